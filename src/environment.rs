@@ -5,7 +5,7 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Default, PartialEq)]
 pub struct Environment {
     values: HashMap<String, Literal>,
     enclosing: Option<Rc<RefCell<Environment>>>,
@@ -30,6 +30,10 @@ impl Environment {
         self.values.insert(name, value);
     }
 
+    pub fn get_value(&self, name: &str) -> Option<Literal> {
+        self.values.get(name).cloned()
+    }
+
     pub fn get(&self, token: &Token) -> Result<Literal, RuntimeError> {
         if let Some(value) = self.values.get(&token.lexeme) {
             return Ok(value.clone());
@@ -37,7 +41,7 @@ impl Environment {
 
         match self.enclosing.as_ref() {
             Some(enclosing) => enclosing.borrow().get(token),
-            None => Err(RuntimeError::UndefinedVariable(token.line, token.clone())),
+            None => Err(RuntimeError::UndefinedVariable(token.clone())),
         }
     }
 
@@ -51,6 +55,38 @@ impl Environment {
             return enclosing.borrow_mut().assign(token, value);
         }
 
-        Err(RuntimeError::UndefinedVariable(token.line, token.clone()))
+        Err(RuntimeError::UndefinedVariable(token.clone()))
+    }
+
+    pub fn get_at(&self, distance: usize, token: &Token) -> Result<Literal, RuntimeError> {
+        if distance == 0 {
+            return self
+                .values
+                .get(&token.lexeme)
+                .cloned()
+                .ok_or_else(|| RuntimeError::UndefinedVariable(token.clone()));
+        }
+
+        match self.enclosing.as_ref() {
+            Some(enclosing) => enclosing.borrow().get_at(distance - 1, token),
+            None => Err(RuntimeError::UndefinedVariable(token.clone())),
+        }
+    }
+
+    pub fn assign_at(
+        &mut self,
+        distance: usize,
+        token: &Token,
+        value: Literal,
+    ) -> Result<Literal, RuntimeError> {
+        if distance == 0 {
+            self.values.insert(token.lexeme.clone(), value.clone());
+            return Ok(value);
+        }
+
+        match self.enclosing.as_ref() {
+            Some(enclosing) => enclosing.borrow_mut().assign_at(distance - 1, token, value),
+            None => Err(RuntimeError::UndefinedVariable(token.clone())),
+        }
     }
 }
@@ -1,8 +1,23 @@
+use crate::models::constants::ExitCode;
+use crate::models::funcs::NamedArgs;
 use crate::models::literals::Literal;
+use crate::models::numbers::ArithmeticError;
 use crate::models::token_type::TokenType;
-use crate::models::tokens::Token;
+use crate::models::tokens::{Span, Token};
 use std::fmt;
 
+#[derive(Debug, Clone)]
+pub struct ScanError {
+    pub span: Span,
+    pub message: String,
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.span.line, self.message)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ParseError {
     pub token: Token,
@@ -12,38 +27,298 @@ pub struct ParseError {
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if self.token.token_type == TokenType::Eof {
-            write!(f, "line {} at end: {}", self.token.line, self.message)
+            write!(f, "line {} at end: {}", self.token.span.line, self.message)
         } else {
             write!(
                 f,
                 "line {} at '{}': {}",
-                self.token.line, self.token.lexeme, self.message
+                self.token.span.line, self.token.lexeme, self.message
             )
         }
     }
 }
 
+/// A call that was in progress when a runtime error was raised: the function,
+/// or `None` for the top-level script, and the line it was running.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub function: Option<String>,
+    pub line: usize,
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.function {
+            Some(name) => write!(f, "[line {}] in {}()", self.line, name),
+            None => write!(f, "[line {}] in script", self.line),
+        }
+    }
+}
+
+// Tracebacks longer than twice this show only this many frames at each end.
+const TRACE_EDGE: usize = 10;
+
 #[derive(Debug, Clone)]
 pub enum RuntimeError {
-    TypeError(usize, String),
-    DivisionByZero(usize, String),
-    UndefinedOperation(usize, String),
-    UndefinedVariable(usize, Token),
-    Return(usize, Literal),
+    TypeError(Span, String),
+    DivisionByZero(Span, String),
+    IntegerOverflow(Span, String),
+    UndefinedOperation(Span, String),
+    UndefinedVariable(Token),
+    UndefinedProperty(Token),
+    Return(Span, Literal),
+    /// A `return f(args);`, handed back for `Function::call_with` to make the
+    /// call once the returning function is done: the callee and its
+    /// positional and named arguments.
+    TailCall(Span, Literal, Vec<Literal>, NamedArgs),
+    Break(Span),
+    Continue(Span),
+    /// Calls nested deeper than the configured maximum, with the chain of
+    /// functions that kept calling each other.
+    StackOverflow(Span, Vec<String>),
+    /// An error raised inside a function call, with the calls that led to
+    /// it, innermost first.
+    Traced(Box<RuntimeError>, Vec<Frame>),
+}
+
+impl RuntimeError {
+    /// Points an error raised without a location, such as by a native
+    /// function, at `span`.
+    pub fn or_at(mut self, span: Span) -> Self {
+        let own = self.span_mut();
+        if *own == Span::default() {
+            *own = span;
+        }
+        self
+    }
+
+    fn span_mut(&mut self) -> &mut Span {
+        match self {
+            RuntimeError::TypeError(span, _)
+            | RuntimeError::DivisionByZero(span, _)
+            | RuntimeError::IntegerOverflow(span, _)
+            | RuntimeError::UndefinedOperation(span, _)
+            | RuntimeError::Return(span, _)
+            | RuntimeError::TailCall(span, ..)
+            | RuntimeError::Break(span)
+            | RuntimeError::Continue(span)
+            | RuntimeError::StackOverflow(span, _) => span,
+            RuntimeError::UndefinedVariable(token) | RuntimeError::UndefinedProperty(token) => {
+                &mut token.span
+            }
+            RuntimeError::Traced(error, _) => error.span_mut(),
+        }
+    }
+
+    /// Attaches the calls that led to the error, innermost first, unless it
+    /// already has them or is not an error at all but a `return`, `break`,
+    /// `continue` or tail call unwinding the stack.
+    pub fn traced(self, trace: impl FnOnce() -> Vec<Frame>) -> Self {
+        match self {
+            RuntimeError::Return(..)
+            | RuntimeError::TailCall(..)
+            | RuntimeError::Break(_)
+            | RuntimeError::Continue(_)
+            | RuntimeError::Traced(..) => self,
+            error => RuntimeError::Traced(Box::new(error), trace()),
+        }
+    }
+
+    /// The calls that led to the error, innermost first. Empty for errors
+    /// raised outside of any function.
+    pub fn trace(&self) -> &[Frame] {
+        match self {
+            RuntimeError::Traced(_, trace) => trace,
+            _ => &[],
+        }
+    }
+
+    /// Describes a failed `left op right` computation.
+    pub fn arithmetic(error: ArithmeticError, span: Span, expression: String) -> Self {
+        match error {
+            ArithmeticError::DivisionByZero => RuntimeError::DivisionByZero(span, expression),
+            ArithmeticError::Overflow => RuntimeError::IntegerOverflow(span, expression),
+            ArithmeticError::InvalidShift => RuntimeError::TypeError(
+                span,
+                format!("Shift amount must be between 0 and 63: {expression}"),
+            ),
+        }
+    }
+
+    /// Reports a call that would nest deeper than allowed, given the names
+    /// of the functions being called, outermost first. Only the cycle the
+    /// innermost calls keep repeating is kept, closed back onto its start,
+    /// as in `isEven -> isOdd -> isEven`.
+    pub fn stack_overflow(span: Span, calls: &[&str]) -> Self {
+        let len = calls.len();
+        let chain = match (1..=len / 2).find(|&k| calls[len - k..] == calls[len - 2 * k..len - k]) {
+            Some(k) => calls[len - k..]
+                .iter()
+                .chain(&calls[len - k..len - k + 1])
+                .collect(),
+            None => calls.last().into_iter().collect::<Vec<_>>(),
+        };
+        RuntimeError::StackOverflow(
+            span,
+            chain.into_iter().map(|name| name.to_string()).collect(),
+        )
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            RuntimeError::TypeError(span, _)
+            | RuntimeError::DivisionByZero(span, _)
+            | RuntimeError::IntegerOverflow(span, _)
+            | RuntimeError::UndefinedOperation(span, _)
+            | RuntimeError::Return(span, _)
+            | RuntimeError::TailCall(span, ..)
+            | RuntimeError::Break(span)
+            | RuntimeError::Continue(span)
+            | RuntimeError::StackOverflow(span, _) => *span,
+            RuntimeError::UndefinedVariable(token) | RuntimeError::UndefinedProperty(token) => {
+                token.span
+            }
+            RuntimeError::Traced(error, _) => error.span(),
+        }
+    }
 }
 
 impl fmt::Display for RuntimeError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let line = self.span().line;
         match self {
-            RuntimeError::TypeError(line, msg) => write!(f, "line {line}: {msg}"),
-            RuntimeError::DivisionByZero(line, msg) => {
+            RuntimeError::TypeError(_, msg) => write!(f, "line {line}: {msg}"),
+            RuntimeError::DivisionByZero(_, msg) => {
                 write!(f, "line {line}: Division by zero: {msg}.")
             }
-            RuntimeError::UndefinedOperation(line, msg) => write!(f, "line {line}: {msg}."),
-            RuntimeError::UndefinedVariable(line, token) => {
+            RuntimeError::IntegerOverflow(_, msg) => {
+                write!(f, "line {line}: Integer overflow: {msg}.")
+            }
+            RuntimeError::UndefinedOperation(_, msg) => write!(f, "line {line}: {msg}."),
+            RuntimeError::UndefinedVariable(token) => {
                 write!(f, "line {line}: Undefined variable '{}'.", token.lexeme)
             }
-            RuntimeError::Return(line, value) => write!(f, "line {line}: Return: {value}"),
+            RuntimeError::UndefinedProperty(token) => {
+                write!(f, "line {line}: Undefined property '{}'.", token.lexeme)
+            }
+            RuntimeError::Return(_, value) => write!(f, "line {line}: Return: {value}"),
+            RuntimeError::TailCall(_, callee, ..) => write!(f, "line {line}: Tail call: {callee}"),
+            RuntimeError::Break(_) => write!(f, "line {line}: Break"),
+            RuntimeError::Continue(_) => write!(f, "line {line}: Continue"),
+            RuntimeError::StackOverflow(_, chain) if chain.is_empty() => {
+                write!(f, "line {line}: Stack overflow.")
+            }
+            RuntimeError::StackOverflow(_, chain) => {
+                write!(f, "line {line}: Stack overflow: {}.", chain.join(" -> "))
+            }
+            RuntimeError::Traced(error, _) => write!(f, "{error}"),
         }
     }
 }
+
+#[derive(Debug, Clone)]
+pub enum LoxError {
+    Scan(Vec<ScanError>),
+    Parse(Vec<ParseError>),
+    Runtime(Vec<RuntimeError>),
+}
+
+impl fmt::Display for LoxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let lines: Vec<String> = match self {
+            LoxError::Scan(errors) => errors.iter().map(ToString::to_string).collect(),
+            LoxError::Parse(errors) => errors.iter().map(ToString::to_string).collect(),
+            LoxError::Runtime(errors) => errors.iter().map(ToString::to_string).collect(),
+        };
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+impl LoxError {
+    /// The code a command-line runner should exit with after this error.
+    pub fn exit_code(&self) -> ExitCode {
+        match self {
+            LoxError::Scan(_) | LoxError::Parse(_) => ExitCode::DataError,
+            LoxError::Runtime(_) => ExitCode::RuntimeError,
+        }
+    }
+
+    /// Renders every error followed by the line of `source` it points at, with
+    /// the offending token underlined, rustc-style. Runtime errors raised in
+    /// a function also list the calls that led to them, innermost first.
+    pub fn render(&self, source: &str) -> String {
+        let diagnostics: Vec<String> = match self {
+            LoxError::Scan(errors) => errors
+                .iter()
+                .map(|error| render_diagnostic(error.to_string(), error.span, source))
+                .collect(),
+            LoxError::Parse(errors) => errors
+                .iter()
+                .map(|error| render_diagnostic(error.to_string(), error.token.span, source))
+                .collect(),
+            LoxError::Runtime(errors) => errors
+                .iter()
+                .map(|error| {
+                    let diagnostic = render_diagnostic(error.to_string(), error.span(), source);
+                    render_trace(diagnostic, error.trace())
+                })
+                .collect(),
+        };
+        diagnostics.join("\n")
+    }
+}
+
+fn render_trace(diagnostic: String, trace: &[Frame]) -> String {
+    let mut lines = vec![diagnostic];
+    if trace.len() <= 2 * TRACE_EDGE {
+        lines.extend(trace.iter().map(Frame::to_string));
+    } else {
+        lines.extend(trace[..TRACE_EDGE].iter().map(Frame::to_string));
+        lines.push(format!("[... {} more calls]", trace.len() - 2 * TRACE_EDGE));
+        lines.extend(
+            trace[trace.len() - TRACE_EDGE..]
+                .iter()
+                .map(Frame::to_string),
+        );
+    }
+    lines.join("\n")
+}
+
+fn render_diagnostic(message: String, span: Span, source: &str) -> String {
+    if span.line == 0 || span.column == 0 {
+        return message;
+    }
+
+    let text = source.lines().nth(span.line - 1).unwrap_or("");
+    let number = span.line.to_string();
+    let gutter = " ".repeat(number.len());
+
+    // Keep tabs so the carets line up with the source as displayed.
+    let indent: String = text
+        .chars()
+        .take(span.column - 1)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+
+    // Underline the whole token, or up to the end of the line when it spans
+    // several lines.
+    let mut bytes = 0;
+    let width = text
+        .chars()
+        .skip(span.column - 1)
+        .take_while(|c| {
+            bytes += c.len_utf8();
+            bytes <= span.length
+        })
+        .count()
+        .max(1);
+
+    let source_line = format!("{number} | {text}");
+    format!(
+        "{message}\n{gutter}--> {}:{}\n{gutter} |\n{}\n{gutter} | {indent}{}",
+        span.line,
+        span.column,
+        source_line.trim_end(),
+        "^".repeat(width)
+    )
+}
@@ -1,20 +1,38 @@
 use crate::environment::Environment;
-use crate::errors::RuntimeError;
+use crate::errors::{Frame, RuntimeError};
+use crate::models::classes::{Class, Instance};
+use crate::models::collections;
+use crate::models::constants::{MAX_CALL_DEPTH, MAX_STACK_USE};
 use crate::models::exr::{Expr, ExprVisitor};
-use crate::models::funcs::Function;
+use crate::models::funcs::{Function, NamedArgs};
 use crate::models::literals::Literal;
+use crate::models::natives;
+use crate::models::numbers::{self, ArithmeticOp, BitwiseOp};
+use crate::models::params::{Param, Signature};
+use crate::models::stack::StackUse;
 use crate::models::stmt::{Stmt, StmtVisitor};
 use crate::models::token_type::TokenType;
-use crate::models::tokens::Token;
+use crate::models::tokens::{Span, Token};
+use indexmap::IndexMap;
 use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::rc::Rc;
-use std::time::{SystemTime, UNIX_EPOCH};
 
 #[allow(dead_code)]
 pub struct Interpreter {
-    pub errors: Vec<String>,
-    pub globals: Rc<RefCell<Environment>>,
+    pub(crate) errors: Vec<RuntimeError>,
+    pub(crate) globals: Rc<RefCell<Environment>>,
     env: Rc<RefCell<Environment>>,
+    locals: HashMap<usize, usize>,
+    // The Lox functions being called, outermost first, with the line each
+    // was called from.
+    calls: Vec<(String, usize)>,
+    // The line of the innermost call made from Lox code. Natives calling
+    // back into Lox count as calling from there.
+    call_line: usize,
+    max_call_depth: usize,
+    stack_use: StackUse,
 }
 
 impl ExprVisitor<Literal> for Interpreter {
@@ -35,7 +53,7 @@ impl ExprVisitor<Literal> for Interpreter {
             TokenType::And if !self.is_truthy(&left_value) => Ok(left_value),
             TokenType::Or | TokenType::And => self.evaluate(right),
             _ => Err(RuntimeError::UndefinedOperation(
-                operator.line,
+                operator.span,
                 format!("Unknown logical operator: {}", operator.token_type),
             )),
         }
@@ -50,117 +68,20 @@ impl ExprVisitor<Literal> for Interpreter {
         let left = self.evaluate(left)?;
         let right = self.evaluate(right)?;
 
-        match operator.token_type {
-            TokenType::Minus => match (&left, &right) {
-                (Literal::Number(l), Literal::Number(r)) => Ok(Literal::Number(l - r)),
-                _ => Err(RuntimeError::TypeError(
-                    operator.line,
-                    format!("Operands must be numbers, got {left} and {right}",),
-                )),
-            },
-            TokenType::Slash => match (&left, &right) {
-                (Literal::Number(l), Literal::Number(r)) => {
-                    if *r == 0.0 {
-                        Err(RuntimeError::DivisionByZero(
-                            operator.line,
-                            format!("{l} / {r}"),
-                        ))
-                    } else {
-                        Ok(Literal::Number(l / r))
-                    }
-                }
-                _ => Err(RuntimeError::TypeError(
-                    operator.line,
-                    format!("Operands must be numbers, got {left} and {right}"),
-                )),
-            },
-            TokenType::Star => match (&left, &right) {
-                (Literal::Number(l), Literal::Number(r)) => Ok(Literal::Number(l * r)),
-                _ => Err(RuntimeError::TypeError(
-                    operator.line,
-                    format!("Operands must be numbers, got {left} and {right}",),
-                )),
-            },
-            TokenType::Plus => match (&left, &right) {
-                (Literal::Number(l), Literal::Number(r)) => Ok(Literal::Number(l + r)),
-                (Literal::String(l), Literal::String(r)) => {
-                    Ok(Literal::String(format!("{}{}", l, r)))
-                }
-                (Literal::String(l), Literal::Number(r)) => {
-                    Ok(Literal::String(format!("{}{}", l, r)))
-                }
-                (Literal::Number(l), Literal::String(r)) => {
-                    Ok(Literal::String(format!("{}{}", l, r)))
-                }
-                _ => Err(RuntimeError::TypeError(
-                    operator.line,
-                    format!("Operands must be numbers or strings, got {left} and {right}"),
-                )),
-            },
-            TokenType::Greater => match (&left, &right) {
-                (Literal::Number(l), Literal::Number(r)) => Ok(Literal::Boolean(l > r)),
-                _ => Err(RuntimeError::TypeError(
-                    operator.line,
-                    format!("Operands must be numbers, got {left} and {right}",),
-                )),
-            },
-            TokenType::GreaterEqual => match (&left, &right) {
-                (Literal::Number(l), Literal::Number(r)) => Ok(Literal::Boolean(l >= r)),
-                _ => Err(RuntimeError::TypeError(
-                    operator.line,
-                    format!("Operands must be numbers, got {left} and {right}",),
-                )),
-            },
-            TokenType::Less => match (&left, &right) {
-                (Literal::Number(l), Literal::Number(r)) => Ok(Literal::Boolean(l < r)),
-                _ => Err(RuntimeError::TypeError(
-                    operator.line,
-                    format!("Operands must be numbers, got {left} and {right}",),
-                )),
-            },
-            TokenType::LessEqual => match (&left, &right) {
-                (Literal::Number(l), Literal::Number(r)) => Ok(Literal::Boolean(l <= r)),
-                _ => Err(RuntimeError::TypeError(
-                    operator.line,
-                    format!("Operands must be numbers, got {left} and {right}",),
-                )),
-            },
-            TokenType::BangEqual => Ok(Literal::Boolean(left != right)),
-            TokenType::EqualEqual => Ok(Literal::Boolean(left == right)),
-            _ => Err(RuntimeError::UndefinedOperation(
-                operator.line,
-                format!("Unknown operator: {}", operator.token_type),
-            )),
-        }
+        self.binary(operator, left, right)
     }
 
     fn visit_call_expr(
         &mut self,
         callee: &Expr,
         paren: &Token,
-        arguments: &Vec<Expr>,
+        arguments: &[Expr],
+        named: &[(Token, Expr)],
     ) -> Result<Literal, RuntimeError> {
         let callee = self.evaluate(callee)?;
-        let args: Vec<Literal> = arguments
-            .iter()
-            .map(|arg| self.evaluate(arg))
-            .collect::<Result<_, _>>()?;
+        let (args, named) = self.evaluate_arguments(arguments, named)?;
 
-        match callee {
-            Literal::Callable(func) => {
-                if args.len() != func.arity() {
-                    return Err(RuntimeError::TypeError(
-                        paren.line,
-                        format!("Expected {} arguments but got {}", func.arity(), args.len()),
-                    ));
-                }
-                func.call(self, args)
-            }
-            _ => Err(RuntimeError::TypeError(
-                paren.line,
-                format!("Can only call functions and classes. Got: {}", callee),
-            )),
-        }
+        self.call_value(&callee, args, named, paren.span)
     }
 
     fn visit_grouping_expr(&mut self, expression: &Expr) -> Result<Literal, RuntimeError> {
@@ -175,28 +96,254 @@ impl ExprVisitor<Literal> for Interpreter {
         let right = self.evaluate(right)?;
 
         match operator.token_type {
-            TokenType::Minus => match right {
-                Literal::Number(n) => Ok(Literal::Number(-n)),
-                _ => Err(RuntimeError::TypeError(
-                    operator.line,
+            TokenType::Minus => match right.as_number() {
+                Some(n) => numbers::negate(n).map(Literal::from).map_err(|error| {
+                    RuntimeError::arithmetic(error, operator.span, format!("-{right}"))
+                }),
+                None => Err(RuntimeError::TypeError(
+                    operator.span,
                     format!("Operand must be a number, got {:?}", right),
                 )),
             },
+            TokenType::Tilde => match right {
+                Literal::Integer(n) => Ok(Literal::Integer(!n)),
+                _ => Err(RuntimeError::TypeError(
+                    operator.span,
+                    format!("Operand must be an integer, got {right}"),
+                )),
+            },
             TokenType::Bang => Ok(Literal::Boolean(!self.is_truthy(&right))),
             _ => Err(RuntimeError::UndefinedOperation(
-                operator.line,
+                operator.span,
                 format!("Unknown operator: {:?}", operator.token_type),
             )),
         }
     }
 
-    fn visit_variable_expr(&mut self, token: &Token) -> Result<Literal, RuntimeError> {
-        self.env.borrow().get(token)
+    fn visit_variable_expr(&mut self, token: &Token, id: usize) -> Result<Literal, RuntimeError> {
+        self.look_up_variable(token, id)
     }
 
-    fn visit_assign_expr(&mut self, token: &Token, expr: &Expr) -> Result<Literal, RuntimeError> {
+    fn visit_assign_expr(
+        &mut self,
+        token: &Token,
+        expr: &Expr,
+        id: usize,
+    ) -> Result<Literal, RuntimeError> {
         let value = self.evaluate(expr)?;
-        self.env.borrow_mut().assign(token, value)
+        self.assign_variable(token, id, value)
+    }
+
+    fn visit_get_expr(&mut self, object: &Expr, name: &Token) -> Result<Literal, RuntimeError> {
+        match self.evaluate(object)? {
+            Literal::Instance(instance) => Instance::get(&instance, name),
+            _ => Err(RuntimeError::TypeError(
+                name.span,
+                "Only instances have properties.".to_string(),
+            )),
+        }
+    }
+
+    fn visit_set_expr(
+        &mut self,
+        object: &Expr,
+        name: &Token,
+        value: &Expr,
+    ) -> Result<Literal, RuntimeError> {
+        match self.evaluate(object)? {
+            Literal::Instance(instance) => {
+                let value = self.evaluate(value)?;
+                instance.borrow_mut().set(name, value.clone());
+                Ok(value)
+            }
+            _ => Err(RuntimeError::TypeError(
+                name.span,
+                "Only instances have fields.".to_string(),
+            )),
+        }
+    }
+
+    fn visit_list_expr(
+        &mut self,
+        _bracket: &Token,
+        elements: &[Expr],
+    ) -> Result<Literal, RuntimeError> {
+        let elements = elements
+            .iter()
+            .map(|element| self.evaluate(element))
+            .collect::<Result<_, _>>()?;
+        Ok(Literal::list(elements))
+    }
+
+    fn visit_interpolation_expr(
+        &mut self,
+        _token: &Token,
+        parts: &[Expr],
+    ) -> Result<Literal, RuntimeError> {
+        let mut text = String::new();
+        for part in parts {
+            text.push_str(&self.evaluate(part)?.to_string());
+        }
+        Ok(Literal::String(text))
+    }
+
+    fn visit_map_expr(
+        &mut self,
+        brace: &Token,
+        entries: &[(Expr, Expr)],
+    ) -> Result<Literal, RuntimeError> {
+        let mut map = IndexMap::with_capacity(entries.len());
+        for (key, value) in entries {
+            let key = self.evaluate(key)?;
+            if !key.is_key() {
+                return Err(RuntimeError::TypeError(
+                    brace.span,
+                    collections::invalid_key(&key),
+                ));
+            }
+            let value = self.evaluate(value)?;
+            map.insert(key, value);
+        }
+        Ok(Literal::map(map))
+    }
+
+    fn visit_index_expr(
+        &mut self,
+        object: &Expr,
+        bracket: &Token,
+        index: &Expr,
+    ) -> Result<Literal, RuntimeError> {
+        let object = self.evaluate(object)?;
+        let index = self.evaluate(index)?;
+        self.get_index(bracket, &object, &index)
+    }
+
+    fn visit_set_index_expr(
+        &mut self,
+        object: &Expr,
+        bracket: &Token,
+        index: &Expr,
+        value: &Expr,
+    ) -> Result<Literal, RuntimeError> {
+        let object = self.evaluate(object)?;
+        let index = self.evaluate(index)?;
+        let value = self.evaluate(value)?;
+        self.set_index(bracket, &object, &index, value.clone())?;
+        Ok(value)
+    }
+
+    fn visit_compound_expr(
+        &mut self,
+        target: &Expr,
+        operator: &Token,
+        value: &Expr,
+        postfix: bool,
+    ) -> Result<Literal, RuntimeError> {
+        let (old, new) = match target {
+            Expr::Variable(token, id) => {
+                let old = self.look_up_variable(token, *id)?;
+                let value = self.evaluate(value)?;
+                let new = self.binary(operator, old.clone(), value)?;
+                (old, self.assign_variable(token, *id, new)?)
+            }
+            Expr::Get(object, name) => {
+                // The object is evaluated once, so `next().count += 1` only
+                // calls `next` a single time.
+                let Literal::Instance(instance) = self.evaluate(object)? else {
+                    return Err(RuntimeError::TypeError(
+                        name.span,
+                        "Only instances have properties.".to_string(),
+                    ));
+                };
+                let old = Instance::get(&instance, name)?;
+                let value = self.evaluate(value)?;
+                let new = self.binary(operator, old.clone(), value)?;
+                instance.borrow_mut().set(name, new.clone());
+                (old, new)
+            }
+            Expr::Index(object, bracket, index) => {
+                let object = self.evaluate(object)?;
+                let index = self.evaluate(index)?;
+                let old = self.get_index(bracket, &object, &index)?;
+                let value = self.evaluate(value)?;
+                let new = self.binary(operator, old.clone(), value)?;
+                self.set_index(bracket, &object, &index, new.clone())?;
+                (old, new)
+            }
+            _ => {
+                return Err(RuntimeError::TypeError(
+                    operator.span,
+                    "Invalid assignment target.".to_string(),
+                ))
+            }
+        };
+
+        Ok(if postfix { old } else { new })
+    }
+
+    fn visit_lambda_expr(
+        &mut self,
+        name: &Token,
+        params: &[Param],
+        body: &Rc<[Stmt]>,
+    ) -> Result<Literal, RuntimeError> {
+        Ok(Literal::Callable(Rc::new(Function::Lox {
+            name: name.lexeme.clone(),
+            params: Rc::from(params),
+            signature: Signature::new(params),
+            body: Rc::clone(body),
+            closure: Rc::clone(&self.env),
+            is_initializer: false,
+        })))
+    }
+
+    fn visit_this_expr(&mut self, keyword: &Token, id: usize) -> Result<Literal, RuntimeError> {
+        self.look_up_variable(keyword, id)
+    }
+
+    fn visit_super_expr(
+        &mut self,
+        keyword: &Token,
+        method: &Token,
+        id: usize,
+    ) -> Result<Literal, RuntimeError> {
+        let Some(&distance) = self.locals.get(&id) else {
+            return Err(RuntimeError::TypeError(
+                keyword.span,
+                "Can't use 'super' outside of a subclass.".to_string(),
+            ));
+        };
+
+        let superclass = match self.env.borrow().get_at(distance, keyword)? {
+            Literal::Class(class) => class,
+            _ => {
+                return Err(RuntimeError::TypeError(
+                    keyword.span,
+                    "Superclass must be a class.".to_string(),
+                ))
+            }
+        };
+
+        let this = Token::new(
+            TokenType::This,
+            "this".to_string(),
+            Literal::Nil,
+            keyword.span,
+        );
+        let instance = match self.env.borrow().get_at(distance - 1, &this)? {
+            Literal::Instance(instance) => instance,
+            _ => {
+                return Err(RuntimeError::TypeError(
+                    keyword.span,
+                    "Can't use 'super' outside of a method.".to_string(),
+                ))
+            }
+        };
+
+        match superclass.find_method(&method.lexeme) {
+            Some(function) => Ok(Literal::Callable(Rc::new(function.bind(instance)))),
+            None => Err(RuntimeError::UndefinedProperty(method.clone())),
+        }
     }
 }
 
@@ -206,18 +353,73 @@ impl StmtVisitor<Result<(), RuntimeError>> for Interpreter {
         Ok(())
     }
 
+    fn visit_class_stmt(
+        &mut self,
+        name: &Token,
+        superclass: &Option<Expr>,
+        methods: &[Stmt],
+    ) -> Result<(), RuntimeError> {
+        let superclass = match superclass {
+            Some(expr) => match self.evaluate(expr)? {
+                Literal::Class(class) => Some(class),
+                _ => {
+                    return Err(RuntimeError::TypeError(
+                        name.span,
+                        "Superclass must be a class.".to_string(),
+                    ))
+                }
+            },
+            None => None,
+        };
+
+        let previous = Rc::clone(&self.env);
+        if let Some(superclass) = &superclass {
+            let mut env = Environment::new_with_enclosing(&self.env);
+            env.define("super".to_string(), Literal::Class(Rc::clone(superclass)));
+            self.env = Rc::new(RefCell::new(env));
+        }
+
+        let mut class_methods = HashMap::new();
+        for method in methods {
+            if let Stmt::Function(method_name, params, body, _) = method {
+                let function = Function::Lox {
+                    name: method_name.lexeme.clone(),
+                    params: Rc::from(params.as_slice()),
+                    signature: Signature::new(params),
+                    body: Rc::clone(body),
+                    closure: Rc::clone(&self.env),
+                    is_initializer: method_name.lexeme == "init",
+                };
+                class_methods.insert(method_name.lexeme.clone(), Rc::new(function));
+            }
+        }
+
+        self.env = previous;
+
+        let class = Literal::Class(Rc::new(Class::new(
+            name.lexeme.clone(),
+            superclass,
+            class_methods,
+        )));
+        self.env.borrow_mut().define(name.lexeme.clone(), class);
+
+        Ok(())
+    }
+
     fn visit_function_stmt(
         &mut self,
         name: &Token,
-        params: &Vec<Token>,
-        body: &Vec<Stmt>,
+        params: &[Param],
+        body: &Rc<[Stmt]>,
     ) -> Result<(), RuntimeError> {
-        let function = Literal::Callable(Function::Lox {
+        let function = Literal::Callable(Rc::new(Function::Lox {
             name: name.lexeme.clone(),
-            params: params.clone(),
-            body: body.clone(),
+            params: Rc::from(params),
+            signature: Signature::new(params),
+            body: Rc::clone(body),
             closure: Rc::clone(&self.env),
-        });
+            is_initializer: false,
+        }));
         self.env.borrow_mut().define(name.lexeme.clone(), function);
 
         Ok(())
@@ -247,97 +449,553 @@ impl StmtVisitor<Result<(), RuntimeError>> for Interpreter {
         Ok(())
     }
 
-    fn visit_while_stmt(&mut self, cond: &Expr, body: &Stmt) -> Result<(), RuntimeError> {
+    fn visit_while_stmt(
+        &mut self,
+        cond: &Expr,
+        body: &Stmt,
+        increment: &Option<Expr>,
+    ) -> Result<(), RuntimeError> {
         loop {
             let condition = self.evaluate(cond)?;
             if !self.is_truthy(&condition) {
                 break;
             }
-            self.execute(body)?;
+
+            match self.execute(body) {
+                Ok(()) | Err(RuntimeError::Continue(_)) => {}
+                Err(RuntimeError::Break(_)) => break,
+                Err(error) => return Err(error),
+            }
+
+            if let Some(increment) = increment {
+                self.evaluate(increment)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_for_in_stmt(
+        &mut self,
+        name: &Token,
+        iterable: &Expr,
+        body: &Stmt,
+    ) -> Result<(), RuntimeError> {
+        let iterable = self.evaluate(iterable)?;
+        let mut iteration = self.iterate(iterable, name.span)?;
+
+        while let Some(item) = self.next_item(&mut iteration, name.span)? {
+            // A fresh environment per pass, so closures capture distinct values.
+            let mut env = Environment::new_with_enclosing(&self.env);
+            env.define(name.lexeme.clone(), item);
+
+            match self.execute_block(std::slice::from_ref(body), Rc::new(RefCell::new(env))) {
+                Ok(()) | Err(RuntimeError::Continue(_)) => {}
+                Err(RuntimeError::Break(_)) => break,
+                Err(error) => return Err(error),
+            }
         }
         Ok(())
     }
 
+    fn visit_break_stmt(&mut self, keyword: &Token) -> Result<(), RuntimeError> {
+        Err(RuntimeError::Break(keyword.span))
+    }
+
+    fn visit_continue_stmt(&mut self, keyword: &Token) -> Result<(), RuntimeError> {
+        Err(RuntimeError::Continue(keyword.span))
+    }
+
     fn visit_return_stmt(
         &mut self,
         token: &Token,
         expr: &Option<Expr>,
     ) -> Result<(), RuntimeError> {
+        // A call in tail position is left for the caller to make, so that
+        // tail recursion doesn't grow the Rust stack.
+        if let Some(Expr::Call(callee, paren, arguments, named)) = expr {
+            let callee = self.evaluate(callee)?;
+            let (args, named) = self.evaluate_arguments(arguments, named)?;
+            return Err(RuntimeError::TailCall(paren.span, callee, args, named));
+        }
+
         let value = expr
             .as_ref()
             .map_or_else(|| Ok(Literal::Nil), |expr| self.evaluate(expr))?;
 
-        Err(RuntimeError::Return(token.line, value))
+        Err(RuntimeError::Return(token.span, value))
     }
 
     fn visit_var_stmt(
         &mut self,
-        lexeme: String,
+        name: &Token,
         initializer: &Option<Expr>,
     ) -> Result<(), RuntimeError> {
         let value = initializer
             .as_ref()
             .map_or_else(|| Ok(Literal::Nil), |expr| self.evaluate(expr))?;
 
-        self.env.borrow_mut().define(lexeme, value);
+        self.env.borrow_mut().define(name.lexeme.clone(), value);
         Ok(())
     }
 
-    fn visit_block_stmt(&mut self, stmts: &Vec<Stmt>) -> Result<(), RuntimeError> {
+    fn visit_block_stmt(&mut self, stmts: &[Stmt]) -> Result<(), RuntimeError> {
         let new_env = Rc::new(RefCell::new(Environment::new_with_enclosing(&self.env)));
         self.execute_block(stmts, new_env)
     }
 }
 
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Interpreter {
     pub fn new() -> Self {
         let globals = Rc::new(RefCell::new(Environment::new()));
 
-        let clock = Literal::Callable(Function::Native {
-            name: "clock".to_string(),
-            arity: 0,
-            body: |_args| {
-                let seconds = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs_f64();
-                Ok(Literal::Number(seconds))
-            },
-        });
-
-        globals.borrow_mut().define("clock".to_string(), clock);
-
-        Self {
+        let mut interpreter = Self {
             errors: Vec::new(),
             globals: Rc::clone(&globals),
             env: Rc::clone(&globals),
+            locals: HashMap::new(),
+            calls: Vec::new(),
+            call_line: 0,
+            max_call_depth: MAX_CALL_DEPTH,
+            stack_use: StackUse::new(MAX_STACK_USE),
+        };
+
+        interpreter.define_builtins();
+        interpreter
+    }
+
+    fn define_builtins(&mut self) {
+        for (name, arity, body) in natives::builtins::<Literal>() {
+            self.define_native(name, arity, move |_, args| {
+                body(&args).map_err(|message| RuntimeError::TypeError(Span::default(), message))
+            });
         }
     }
 
-    pub fn interpret(&mut self, stmts: &[Stmt]) {
+    /// Runs `stmts` and returns the value of the last one when it is an
+    /// expression statement, `nil` otherwise.
+    pub(crate) fn interpret(&mut self, stmts: &[Stmt]) -> Literal {
+        let mut last = Literal::Nil;
+
         for stmt in stmts {
-            match self.execute(stmt) {
-                Ok(_) => {}
-                Err(RuntimeError::Return(..)) => {}
-                Err(e) => self.log_error(e),
+            let result = match stmt {
+                Stmt::Expr(expr) => self.evaluate(expr),
+                _ => self.execute(stmt).map(|_| Literal::Nil),
+            };
+
+            last = match result {
+                Ok(value) => value,
+                Err(RuntimeError::Return(..)) => Literal::Nil,
+                Err(e) => {
+                    self.log_error(e);
+                    Literal::Nil
+                }
+            };
+        }
+
+        last
+    }
+
+    pub fn define_global(&mut self, name: &str, value: Literal) {
+        self.globals.borrow_mut().define(name.to_string(), value);
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Literal> {
+        self.globals.borrow().get_value(name)
+    }
+
+    /// Registers a global native function. `body` may capture Rust state and
+    /// receives the interpreter, so it can call back into Lox with `call`.
+    pub fn define_native<F>(&mut self, name: &str, arity: usize, body: F)
+    where
+        F: Fn(&mut Interpreter, Vec<Literal>) -> Result<Literal, RuntimeError> + 'static,
+    {
+        let native = Function::Native {
+            name: name.to_string(),
+            arity,
+            body: Rc::new(body),
+        };
+        self.define_global(name, Literal::Callable(Rc::new(native)));
+    }
+
+    /// Calls a Lox function or class from Rust.
+    pub fn call(&mut self, callee: &Literal, args: Vec<Literal>) -> Result<Literal, RuntimeError> {
+        self.call_value(callee, args, Vec::new(), Span::default())
+    }
+
+    /// Sets how deeply Lox calls may nest before they fail with a stack
+    /// overflow. Each level takes Rust stack, so the thread running the
+    /// interpreter must have room for that many.
+    pub(crate) fn set_max_call_depth(&mut self, depth: usize) {
+        self.max_call_depth = depth;
+    }
+
+    pub(crate) fn set_max_stack_use(&mut self, bytes: usize) {
+        self.stack_use.set_limit(bytes);
+    }
+
+    /// Runs `f` as the body of a call to the Lox function `name`, failing
+    /// instead when that would nest calls deeper, or use more native stack,
+    /// than allowed. Errors leaving the call take a snapshot of the calls
+    /// that led to them.
+    pub(crate) fn in_call<T>(
+        &mut self,
+        name: &str,
+        f: impl FnOnce(&mut Self) -> Result<T, RuntimeError>,
+    ) -> Result<T, RuntimeError> {
+        if self.calls.is_empty() {
+            self.stack_use.start();
+        }
+        if self.calls.len() >= self.max_call_depth || self.stack_use.exhausted() {
+            let mut calls: Vec<&str> = self.calls.iter().map(|(name, _)| name.as_str()).collect();
+            calls.push(name);
+            return Err(RuntimeError::stack_overflow(Span::default(), &calls));
+        }
+
+        self.calls.push((name.to_string(), self.call_line));
+        let result = f(self).map_err(|error| {
+            let line = error.span().line;
+            error.traced(|| self.trace(line))
+        });
+        self.calls.pop();
+        result
+    }
+
+    /// The calls in progress, innermost first, for an error raised on `line`.
+    fn trace(&self, mut line: usize) -> Vec<Frame> {
+        let mut frames = Vec::with_capacity(self.calls.len() + 1);
+        for (name, call_line) in self.calls.iter().rev() {
+            frames.push(Frame {
+                function: Some(name.clone()),
+                line,
+            });
+            line = *call_line;
+        }
+        // Calls made from Rust rather than from a script have no line.
+        if line != 0 {
+            frames.push(Frame {
+                function: None,
+                line,
+            });
+        }
+        frames
+    }
+
+    pub(crate) fn call_value(
+        &mut self,
+        callee: &Literal,
+        args: Vec<Literal>,
+        named: NamedArgs,
+        span: Span,
+    ) -> Result<Literal, RuntimeError> {
+        let caller_line = self.call_line;
+        if span.line != 0 {
+            self.call_line = span.line;
+        }
+
+        let result = match callee {
+            Literal::Callable(func) => func
+                .call_with(self, args, named)
+                .map_err(|error| error.or_at(span)),
+            Literal::Class(class) => class
+                .call(self, args, named)
+                .map_err(|error| error.or_at(span)),
+            _ => Err(RuntimeError::TypeError(
+                span,
+                format!("Can only call functions and classes. Got: {}", callee),
+            )),
+        };
+
+        self.call_line = caller_line;
+        result
+    }
+
+    /// Starts a for-in loop over `iterable`. Lists are read live, so elements
+    /// pushed during the loop are visited; map keys and string characters
+    /// are taken up front, and ranges count lazily. Instances provide an iterator object through an
+    /// `iterator()` method.
+    fn iterate(&mut self, iterable: Literal, span: Span) -> Result<Iteration, RuntimeError> {
+        match iterable {
+            Literal::List(elements) => Ok(Iteration::List(elements, 0)),
+            Literal::Map(entries) => {
+                let keys: Vec<Literal> = entries.borrow().keys().cloned().collect();
+                Ok(Iteration::Snapshot(keys.into_iter()))
+            }
+            Literal::String(s) => {
+                let chars: Vec<Literal> = s.chars().map(|c| Literal::String(c.into())).collect();
+                Ok(Iteration::Snapshot(chars.into_iter()))
             }
+            Literal::Range(range) => Ok(Iteration::Range(range)),
+            Literal::Instance(instance) => match self.invoke(&instance, "iterator", span)? {
+                Literal::Instance(iterator) => Ok(Iteration::Object(iterator)),
+                _ => Err(RuntimeError::TypeError(
+                    span,
+                    "iterator() must return an object with hasNext() and next().".to_string(),
+                )),
+            },
+            other => Err(RuntimeError::TypeError(
+                span,
+                format!("Can't iterate over {other}."),
+            )),
+        }
+    }
+
+    /// Produces the next value of a for-in loop, asking iterator objects
+    /// through their `hasNext()` and `next()` methods.
+    fn next_item(
+        &mut self,
+        iteration: &mut Iteration,
+        span: Span,
+    ) -> Result<Option<Literal>, RuntimeError> {
+        match iteration {
+            Iteration::List(elements, next) => {
+                let item = elements.borrow().get(*next).cloned();
+                *next += 1;
+                Ok(item)
+            }
+            Iteration::Snapshot(items) => Ok(items.next()),
+            Iteration::Range(range) => Ok(range.next().map(Literal::Integer)),
+            Iteration::Object(iterator) => {
+                let iterator = Rc::clone(iterator);
+                let has_next = self.invoke(&iterator, "hasNext", span)?;
+                if !self.is_truthy(&has_next) {
+                    return Ok(None);
+                }
+                self.invoke(&iterator, "next", span).map(Some)
+            }
+        }
+    }
+
+    /// Calls the method `name` of `instance` without arguments.
+    fn invoke(
+        &mut self,
+        instance: &Rc<RefCell<Instance>>,
+        name: &str,
+        span: Span,
+    ) -> Result<Literal, RuntimeError> {
+        let token = Token::new(TokenType::Identifier, name.to_string(), Literal::Nil, span);
+        let method = Instance::get(instance, &token)?;
+        self.call_value(&method, Vec::new(), Vec::new(), span)
+    }
+
+    pub(crate) fn resolve(&mut self, id: usize, depth: usize) {
+        self.locals.insert(id, depth);
+    }
+
+    fn look_up_variable(&self, token: &Token, id: usize) -> Result<Literal, RuntimeError> {
+        match self.locals.get(&id) {
+            Some(distance) => self.env.borrow().get_at(*distance, token),
+            None => self.globals.borrow().get(token),
+        }
+    }
+
+    fn assign_variable(
+        &mut self,
+        token: &Token,
+        id: usize,
+        value: Literal,
+    ) -> Result<Literal, RuntimeError> {
+        match self.locals.get(&id) {
+            Some(distance) => self.env.borrow_mut().assign_at(*distance, token, value),
+            None => self.globals.borrow_mut().assign(token, value),
         }
     }
 
     fn log_error(&mut self, error: RuntimeError) {
-        self.errors.push(error.to_string());
+        self.errors.push(error);
     }
 
     fn evaluate(&mut self, expression: &Expr) -> Result<Literal, RuntimeError> {
         expression.accept(self)
     }
 
+    /// Applies a binary operator to two evaluated operands. Compound
+    /// assignments reuse this with the operator they desugar to.
+    fn binary(
+        &self,
+        operator: &Token,
+        left: Literal,
+        right: Literal,
+    ) -> Result<Literal, RuntimeError> {
+        match operator.token_type {
+            TokenType::Plus => match (&left, &right) {
+                (
+                    Literal::String(_),
+                    Literal::String(_) | Literal::Number(_) | Literal::Integer(_),
+                )
+                | (Literal::Number(_) | Literal::Integer(_), Literal::String(_)) => {
+                    Ok(Literal::String(format!("{}{}", left, right)))
+                }
+                _ => self.arithmetic(ArithmeticOp::Add, operator, &left, &right),
+            },
+            TokenType::Minus => self.arithmetic(ArithmeticOp::Subtract, operator, &left, &right),
+            TokenType::Star => self.arithmetic(ArithmeticOp::Multiply, operator, &left, &right),
+            TokenType::Slash => self.arithmetic(ArithmeticOp::Divide, operator, &left, &right),
+            TokenType::TildeSlash => {
+                self.arithmetic(ArithmeticOp::IntDivide, operator, &left, &right)
+            }
+            TokenType::Percent => self.arithmetic(ArithmeticOp::Modulo, operator, &left, &right),
+            TokenType::StarStar => self.arithmetic(ArithmeticOp::Power, operator, &left, &right),
+            TokenType::Ampersand => self.bitwise(BitwiseOp::And, operator, &left, &right),
+            TokenType::Pipe => self.bitwise(BitwiseOp::Or, operator, &left, &right),
+            TokenType::Caret => self.bitwise(BitwiseOp::Xor, operator, &left, &right),
+            TokenType::LessLess => self.bitwise(BitwiseOp::ShiftLeft, operator, &left, &right),
+            TokenType::GreaterGreater => {
+                self.bitwise(BitwiseOp::ShiftRight, operator, &left, &right)
+            }
+            TokenType::Greater => self.compare(operator, &left, &right, Ordering::is_gt),
+            TokenType::GreaterEqual => self.compare(operator, &left, &right, Ordering::is_ge),
+            TokenType::Less => self.compare(operator, &left, &right, Ordering::is_lt),
+            TokenType::LessEqual => self.compare(operator, &left, &right, Ordering::is_le),
+            TokenType::BangEqual => Ok(Literal::Boolean(left != right)),
+            TokenType::EqualEqual => Ok(Literal::Boolean(left == right)),
+            _ => Err(RuntimeError::UndefinedOperation(
+                operator.span,
+                format!("Unknown operator: {}", operator.token_type),
+            )),
+        }
+    }
+
+    fn arithmetic(
+        &self,
+        op: ArithmeticOp,
+        operator: &Token,
+        left: &Literal,
+        right: &Literal,
+    ) -> Result<Literal, RuntimeError> {
+        match (left.as_number(), right.as_number()) {
+            (Some(l), Some(r)) => op.apply(l, r).map(Literal::from).map_err(|error| {
+                let expression = format!("{left} {} {right}", op.symbol());
+                RuntimeError::arithmetic(error, operator.span, expression)
+            }),
+            _ if op == ArithmeticOp::Add => Err(RuntimeError::TypeError(
+                operator.span,
+                format!("Operands must be numbers or strings, got {left} and {right}"),
+            )),
+            _ => Err(RuntimeError::TypeError(
+                operator.span,
+                format!("Operands must be numbers, got {left} and {right}"),
+            )),
+        }
+    }
+
+    fn get_index(
+        &self,
+        bracket: &Token,
+        object: &Literal,
+        index: &Literal,
+    ) -> Result<Literal, RuntimeError> {
+        let error = |message: String| RuntimeError::TypeError(bracket.span, message);
+
+        let position = |len: usize| {
+            let index = natives::index_arg(index).map_err(error)?;
+            collections::position(index, len).map_err(error)
+        };
+
+        match object {
+            Literal::List(elements) => {
+                let elements = elements.borrow();
+                Ok(elements[position(elements.len())?].clone())
+            }
+            Literal::String(s) => {
+                let at = position(s.chars().count())?;
+                Ok(Literal::String(
+                    s.chars().nth(at).unwrap_or_default().to_string(),
+                ))
+            }
+            Literal::Map(entries) => match entries.borrow().get(index) {
+                Some(value) => Ok(value.clone()),
+                None if index.is_key() => Err(error(collections::undefined_key(index))),
+                None => Err(error(collections::invalid_key(index))),
+            },
+            _ => Err(error(
+                "Only lists, maps and strings can be indexed.".to_string(),
+            )),
+        }
+    }
+
+    fn set_index(
+        &self,
+        bracket: &Token,
+        object: &Literal,
+        index: &Literal,
+        value: Literal,
+    ) -> Result<(), RuntimeError> {
+        let error = |message: String| RuntimeError::TypeError(bracket.span, message);
+
+        match object {
+            Literal::List(elements) => {
+                let index = natives::index_arg(index).map_err(error)?;
+                let mut elements = elements.borrow_mut();
+                let at = collections::position(index, elements.len()).map_err(error)?;
+                elements[at] = value;
+            }
+            Literal::Map(entries) if index.is_key() => {
+                entries.borrow_mut().insert(index.clone(), value);
+            }
+            Literal::Map(_) => return Err(error(collections::invalid_key(index))),
+            _ => {
+                return Err(error(
+                    "Only list and map elements can be assigned.".to_string(),
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    fn bitwise(
+        &self,
+        op: BitwiseOp,
+        operator: &Token,
+        left: &Literal,
+        right: &Literal,
+    ) -> Result<Literal, RuntimeError> {
+        match (left, right) {
+            (Literal::Integer(l), Literal::Integer(r)) => {
+                op.apply(*l, *r).map(Literal::Integer).map_err(|error| {
+                    let expression = format!("{left} {} {right}", op.symbol());
+                    RuntimeError::arithmetic(error, operator.span, expression)
+                })
+            }
+            _ => Err(RuntimeError::TypeError(
+                operator.span,
+                format!("Operands must be integers, got {left} and {right}"),
+            )),
+        }
+    }
+
+    fn compare(
+        &self,
+        operator: &Token,
+        left: &Literal,
+        right: &Literal,
+        test: fn(Ordering) -> bool,
+    ) -> Result<Literal, RuntimeError> {
+        match (left.as_number(), right.as_number()) {
+            (Some(l), Some(r)) => Ok(Literal::Boolean(numbers::compare(l, r).is_some_and(test))),
+            _ => Err(RuntimeError::TypeError(
+                operator.span,
+                format!("Operands must be numbers, got {left} and {right}"),
+            )),
+        }
+    }
+
     fn is_truthy(&self, literal: &Literal) -> bool {
         match literal {
             Literal::Nil => false,
             Literal::Boolean(b) => *b,
+            Literal::Integer(n) => *n != 0,
             Literal::Number(n) => *n != 0.0,
             Literal::String(s) => !s.is_empty(),
+            Literal::List(elements) => !elements.borrow().is_empty(),
+            Literal::Map(entries) => !entries.borrow().is_empty(),
+            Literal::Range(range) => !range.is_empty(),
+            Literal::Class(_) | Literal::Instance(_) => true,
             _ => false,
         }
     }
@@ -346,7 +1004,35 @@ impl Interpreter {
         stmt.accept(self)
     }
 
-    pub fn execute_block(
+    fn evaluate_arguments(
+        &mut self,
+        arguments: &[Expr],
+        named: &[(Token, Expr)],
+    ) -> Result<(Vec<Literal>, NamedArgs), RuntimeError> {
+        let args = arguments
+            .iter()
+            .map(|arg| self.evaluate(arg))
+            .collect::<Result<_, _>>()?;
+        let named = named
+            .iter()
+            .map(|(name, arg)| Ok((name.lexeme.clone(), self.evaluate(arg)?)))
+            .collect::<Result<_, RuntimeError>>()?;
+        Ok((args, named))
+    }
+
+    /// Evaluates `expression` in `env`, as for a parameter default.
+    pub(crate) fn evaluate_in(
+        &mut self,
+        expression: &Expr,
+        env: Rc<RefCell<Environment>>,
+    ) -> Result<Literal, RuntimeError> {
+        let previous = std::mem::replace(&mut self.env, env);
+        let result = self.evaluate(expression);
+        self.env = previous;
+        result
+    }
+
+    pub(crate) fn execute_block(
         &mut self,
         stmts: &[Stmt],
         env: Rc<RefCell<Environment>>,
@@ -366,3 +1052,11 @@ impl Interpreter {
         result
     }
 }
+
+/// Where a for-in loop takes its values from.
+enum Iteration {
+    List(Rc<RefCell<Vec<Literal>>>, usize),
+    Snapshot(std::vec::IntoIter<Literal>),
+    Object(Rc<RefCell<Instance>>),
+    Range(std::ops::Range<i64>),
+}
@@ -0,0 +1,33 @@
+//! Lox language interpreter.
+//!
+//! ```
+//! use lox_interpreter_rust::{Literal, Lox};
+//!
+//! let mut lox = Lox::new();
+//! lox.define_global("base", Literal::Number(40.0));
+//! lox.run("fun add(n) { return base + n; }").unwrap();
+//!
+//! let result = lox.call("add", vec![Literal::Number(2.0)]).unwrap();
+//! assert_eq!(result, Literal::Number(42.0));
+//! ```
+
+mod environment;
+mod errors;
+mod interpreter;
+mod lox;
+mod models;
+mod parser;
+mod resolver;
+mod scanner;
+mod vm;
+
+pub use errors::{Frame, LoxError, RuntimeError};
+pub use interpreter::Interpreter;
+pub use lox::{decode_source, parse, Backend, Lox};
+pub use models::constants::ExitCode;
+pub use models::exr::{Expr, ExprVisitor};
+pub use models::literals::Literal;
+pub use models::params::Param;
+pub use models::stmt::{Stmt, StmtVisitor};
+pub use models::token_type::TokenType;
+pub use models::tokens::{Span, Token};
@@ -1,57 +1,205 @@
-use std::process::exit;
-
+use crate::errors::{LoxError, RuntimeError, ScanError};
 use crate::interpreter::Interpreter;
+use crate::models::literals::Literal;
+use crate::models::stmt::Stmt;
+use crate::models::token_type::TokenType;
+use crate::models::tokens::{Span, Token};
 use crate::parser::Parser;
-use crate::{models::constants::ExitCode, scanner::Scanner};
+use crate::resolver::Resolver;
+use crate::scanner::Scanner;
+use crate::vm::{Value, Vm};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    TreeWalker,
+    Vm,
+}
+
+/// Decodes the bytes of a script. Invalid UTF-8 gives a scan error pointing
+/// at the first invalid sequence, along with a lossy copy of the source to
+/// render it against.
+pub fn decode_source(bytes: Vec<u8>) -> Result<String, (String, LoxError)> {
+    let error = match String::from_utf8(bytes) {
+        Ok(source_code) => return Ok(source_code),
+        Err(error) => error,
+    };
+
+    let bytes = error.as_bytes();
+    let offset = error.utf8_error().valid_up_to();
+    // Everything before `offset` is valid, so this cannot fail.
+    let valid = std::str::from_utf8(&bytes[..offset]).unwrap_or_default();
+    let line_start = valid.rfind('\n').map_or(0, |index| index + 1);
+    let span = Span::new(
+        valid.matches('\n').count() + 1,
+        valid[line_start..].chars().count() + 1,
+        offset,
+        error
+            .utf8_error()
+            .error_len()
+            .unwrap_or(bytes.len() - offset),
+    );
+
+    let scan_error = LoxError::Scan(vec![ScanError {
+        span,
+        message: "Source is not valid UTF-8.".to_string(),
+    }]);
+    Err((String::from_utf8_lossy(bytes).into_owned(), scan_error))
+}
+
+/// Scans and parses `source_code` without running it, for tools that work on
+/// the syntax tree, such as documentation generators.
+pub fn parse(source_code: &str) -> Result<Vec<Stmt>, LoxError> {
+    let mut scanner = Scanner::new(source_code.to_string());
+    scanner.scan_tokens();
+
+    if !scanner.errors.is_empty() {
+        return Err(LoxError::Scan(scanner.errors));
+    }
 
-#[derive(Debug, Clone, Copy)]
-pub struct Lox {}
+    let mut parser = Parser::new(scanner.tokens);
+    parser.parse();
+
+    if !parser.errors.is_empty() {
+        return Err(LoxError::Parse(parser.errors));
+    }
+
+    Ok(parser.stmts)
+}
+
+pub struct Lox {
+    backend: Backend,
+    interpreter: Interpreter,
+    vm: Vm,
+}
+
+impl Default for Lox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Lox {
     pub fn new() -> Self {
-        Self {}
+        Self::with_backend(Backend::TreeWalker)
+    }
+
+    pub fn with_backend(backend: Backend) -> Self {
+        Self {
+            backend,
+            interpreter: Interpreter::new(),
+            vm: Vm::new(),
+        }
+    }
+
+    /// Runs `source_code` and returns the value of its last statement when
+    /// that is an expression statement, `nil` otherwise. State such as globals
+    /// is kept between calls.
+    pub fn run(&mut self, source_code: &str) -> Result<Literal, LoxError> {
+        let stmts = parse(source_code)?;
+
+        // The VM resolves variables itself, so it only needs the errors.
+        let interpreter = match self.backend {
+            Backend::TreeWalker => Some(&mut self.interpreter),
+            Backend::Vm => None,
+        };
+        let mut resolver = Resolver::new(interpreter);
+        resolver.resolve(&stmts);
+
+        if !resolver.errors.is_empty() {
+            return Err(LoxError::Parse(resolver.errors));
+        }
+
+        let (value, errors) = match self.backend {
+            Backend::TreeWalker => {
+                let value = self.interpreter.interpret(&stmts);
+                (value, &mut self.interpreter.errors)
+            }
+            Backend::Vm => {
+                let scripts = Vm::compile(&stmts).map_err(LoxError::Parse)?;
+                let value = self.vm.interpret(&scripts);
+                (value, &mut self.vm.errors)
+            }
+        };
+
+        if !errors.is_empty() {
+            return Err(LoxError::Runtime(std::mem::take(errors)));
+        }
+
+        Ok(value)
     }
 
-    #[allow(dead_code)]
-    fn error(&mut self, line: usize, message: &str) {
-        self.report(line, "", message);
+    /// Sets how deeply Lox calls may nest before they fail with a "Stack
+    /// overflow" runtime error.
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.interpreter.set_max_call_depth(depth);
+        self.vm.set_max_call_depth(depth);
     }
 
-    fn report(&mut self, line: usize, location: &str, message: &str) {
-        eprintln!("[line {}] Error: {}: {}", line, location, message);
+    /// Sets how many bytes of native stack nested Lox calls may use before
+    /// they fail with a "Stack overflow" runtime error. The tree-walker uses
+    /// native stack for every call, so raising this needs a thread with a
+    /// stack at least as large.
+    pub fn set_max_stack_use(&mut self, bytes: usize) {
+        self.interpreter.set_max_stack_use(bytes);
+        self.vm.set_max_stack_use(bytes);
     }
 
-    pub fn run(&self, source_code: &str) {
-        let mut scanner = Scanner::new(source_code.to_string());
-        scanner.scan_tokens();
+    /// Defines a global variable visible to every script run afterwards. The
+    /// VM backend takes copies of lists and maps, and turns functions,
+    /// classes and instances into `nil`.
+    pub fn define_global(&mut self, name: &str, value: Literal) {
+        match self.backend {
+            Backend::TreeWalker => self.interpreter.define_global(name, value),
+            Backend::Vm => self.vm.define_global(name, Value::from_literal(&value)),
+        }
+    }
 
-        if !scanner.errors.is_empty() {
-            println!("Errors:");
-            for error in scanner.errors {
-                eprintln!("{}", error);
-            }
-            exit(ExitCode::DataError as i32);
+    /// Reads a global variable back. On the VM backend functions, classes and
+    /// instances come back as `nil`.
+    pub fn get_global(&self, name: &str) -> Option<Literal> {
+        match self.backend {
+            Backend::TreeWalker => self.interpreter.get_global(name),
+            Backend::Vm => self.vm.get_global(name).map(|value| value.to_literal()),
         }
+    }
 
-        let mut parser = Parser::new(scanner.tokens);
-        parser.parse();
+    /// Calls the global function or class `name` with `args`.
+    pub fn call(&mut self, name: &str, args: Vec<Literal>) -> Result<Literal, LoxError> {
+        let undefined = || {
+            let token = Token::new(
+                TokenType::Identifier,
+                name.to_string(),
+                Literal::Nil,
+                Span::default(),
+            );
+            LoxError::Runtime(vec![RuntimeError::UndefinedVariable(token)])
+        };
 
-        if !parser.errors.is_empty() {
-            println!("Errors:");
-            for error in parser.errors {
-                eprintln!("{}", error);
+        match self.backend {
+            Backend::TreeWalker => {
+                let callee = self.interpreter.get_global(name).ok_or_else(undefined)?;
+                self.interpreter
+                    .call(&callee, args)
+                    .map_err(|error| LoxError::Runtime(vec![error]))
+            }
+            Backend::Vm => {
+                let callee = self.vm.get_global(name).ok_or_else(undefined)?;
+                let args = args.iter().map(Value::from_literal).collect();
+                self.vm
+                    .call_function(callee, args)
+                    .map(|value| value.to_literal())
+                    .map_err(|error| LoxError::Runtime(vec![error]))
             }
-            exit(ExitCode::DataError as i32);
         }
+    }
 
-        let mut interpreter = Interpreter::new();
-        interpreter.interpret(&parser.stmts);
-        if !interpreter.errors.is_empty() {
-            println!("Runtime errors:");
-            for error in interpreter.errors {
-                eprintln!("{}", error);
-            }
-            exit(ExitCode::RuntimeError as i32);
+    /// The tree-walking interpreter behind this session, for lower-level access
+    /// such as registering natives with `Interpreter::define_native`. `None`
+    /// on the VM backend, which only runs its built-in natives.
+    pub fn interpreter(&mut self) -> Option<&mut Interpreter> {
+        match self.backend {
+            Backend::TreeWalker => Some(&mut self.interpreter),
+            Backend::Vm => None,
         }
     }
 }
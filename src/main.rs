@@ -1,26 +1,95 @@
-mod environment;
-mod errors;
-mod interpreter;
-mod lox;
-mod models;
-mod parser;
-mod scanner;
-
-use lox::Lox;
-use models::constants::ExitCode;
-use std::{env, fs, process::exit};
+use lox_interpreter_rust::{decode_source, Backend, ExitCode, Lox, LoxError};
+use std::io::{self, BufRead, Write};
+use std::{env, fs, process::exit, thread};
+
+const USAGE: &str = "Usage: rlox.sh [--backend=tree|vm] [script]";
+
+// The tree-walker recurses on the Rust stack for every Lox call, so it runs
+// on a large thread and lets Lox calls use most of it.
+const STACK_SIZE: usize = 256 * 1024 * 1024;
 
 fn main() {
-    let filename = env::args().nth(1).unwrap_or_else(|| {
-        eprintln!("Usage: rlox.sh [script]");
-        exit(ExitCode::IncorrectCommand as i32);
-    });
+    let runner = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(run)
+        .expect("failed to start the interpreter thread");
+    if runner.join().is_err() {
+        exit(ExitCode::RuntimeError as i32);
+    }
+}
+
+fn run() {
+    let mut backend = Backend::TreeWalker;
+    let mut filename = None;
+
+    for arg in env::args().skip(1) {
+        match arg.strip_prefix("--backend=") {
+            Some("tree") => backend = Backend::TreeWalker,
+            Some("vm") => backend = Backend::Vm,
+            Some(_) => usage(),
+            None if filename.is_none() && !arg.starts_with("--") => filename = Some(arg),
+            None => usage(),
+        }
+    }
+
+    let mut lox = Lox::with_backend(backend);
+    lox.set_max_stack_use(STACK_SIZE / 2);
+
+    match filename {
+        None => run_prompt(&mut lox),
+        Some(filename) => run_file(&mut lox, &filename),
+    }
+}
 
-    let source_code = fs::read_to_string(&filename).unwrap_or_else(|_| {
+fn run_file(lox: &mut Lox, filename: &str) {
+    let bytes = fs::read(filename).unwrap_or_else(|_| {
         eprintln!("Error reading file '{}'", filename);
         exit(ExitCode::NoInputFile as i32);
     });
 
-    let lox = Lox::new();
-    lox.run(&source_code);
+    let source_code = match decode_source(bytes) {
+        Ok(source_code) => source_code,
+        Err((source_code, error)) => {
+            report(&error, &source_code);
+            exit(error.exit_code() as i32);
+        }
+    };
+
+    if let Err(error) = lox.run(&source_code) {
+        report(&error, &source_code);
+        exit(error.exit_code() as i32);
+    }
+}
+
+fn run_prompt(lox: &mut Lox) {
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+
+    loop {
+        print!("> ");
+        io::stdout().flush().unwrap_or(());
+
+        match lines.next() {
+            // Errors are reported, the session keeps going.
+            Some(Ok(line)) => {
+                if let Err(error) = lox.run(&line) {
+                    report(&error, &line);
+                }
+            }
+            _ => break,
+        }
+    }
+}
+
+fn report(error: &LoxError, source_code: &str) {
+    match error {
+        LoxError::Scan(_) | LoxError::Parse(_) => println!("Errors:"),
+        LoxError::Runtime(_) => println!("Runtime errors:"),
+    }
+    eprintln!("{}", error.render(source_code));
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    exit(ExitCode::IncorrectCommand as i32);
 }
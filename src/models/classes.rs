@@ -0,0 +1,125 @@
+use crate::errors::RuntimeError;
+use crate::interpreter::Interpreter;
+use crate::models::funcs::{Function, NamedArgs};
+use crate::models::literals::Literal;
+use crate::models::params::Signature;
+use crate::models::tokens::{Span, Token};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+
+#[derive(Debug)]
+pub struct Class {
+    pub name: String,
+    pub superclass: Option<Rc<Class>>,
+    pub methods: HashMap<String, Rc<Function>>,
+}
+
+#[derive(Debug)]
+pub struct Instance {
+    pub class: Rc<Class>,
+    fields: HashMap<String, Literal>,
+}
+
+impl PartialEq for Class {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl PartialEq for Instance {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Display for Class {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl Display for Instance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} instance", self.class.name)
+    }
+}
+
+impl Class {
+    pub fn new(
+        name: String,
+        superclass: Option<Rc<Class>>,
+        methods: HashMap<String, Rc<Function>>,
+    ) -> Self {
+        Self {
+            name,
+            superclass,
+            methods,
+        }
+    }
+
+    pub fn find_method(&self, name: &str) -> Option<Rc<Function>> {
+        if let Some(method) = self.methods.get(name) {
+            return Some(Rc::clone(method));
+        }
+
+        self.superclass
+            .as_ref()
+            .and_then(|superclass| superclass.find_method(name))
+    }
+
+    pub fn arity(&self) -> usize {
+        self.find_method("init").map_or(0, |init| init.arity())
+    }
+
+    pub fn call(
+        self: &Rc<Self>,
+        interpreter: &mut Interpreter,
+        args: Vec<Literal>,
+        named: NamedArgs,
+    ) -> Result<Literal, RuntimeError> {
+        let instance = Rc::new(RefCell::new(Instance::new(Rc::clone(self))));
+
+        match self.find_method("init") {
+            Some(initializer) => {
+                initializer
+                    .bind(Rc::clone(&instance))
+                    .call_with(interpreter, args, named)?;
+            }
+            // Without an initializer the class takes no arguments at all.
+            None => {
+                Signature::default()
+                    .bind(args, named)
+                    .map_err(|message| RuntimeError::TypeError(Span::default(), message))?;
+            }
+        }
+
+        Ok(Literal::Instance(instance))
+    }
+}
+
+impl Instance {
+    pub fn new(class: Rc<Class>) -> Self {
+        Self {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+
+    pub fn get(instance: &Rc<RefCell<Instance>>, name: &Token) -> Result<Literal, RuntimeError> {
+        if let Some(value) = instance.borrow().fields.get(&name.lexeme) {
+            return Ok(value.clone());
+        }
+
+        let method = instance.borrow().class.find_method(&name.lexeme);
+        match method {
+            Some(method) => Ok(Literal::Callable(Rc::new(method.bind(Rc::clone(instance))))),
+            None => Err(RuntimeError::UndefinedProperty(name.clone())),
+        }
+    }
+
+    pub fn set(&mut self, name: &Token, value: Literal) {
+        self.fields.insert(name.lexeme.clone(), value);
+    }
+}
@@ -0,0 +1,32 @@
+//! Index and key checks for list and map operations.
+
+use std::fmt::Display;
+
+/// Converts `index` into a position in a list of `len` elements.
+pub fn position(index: i64, len: usize) -> Result<usize, String> {
+    usize::try_from(index)
+        .ok()
+        .filter(|&i| i < len)
+        .ok_or_else(|| out_of_range(index, len))
+}
+
+/// Like `position`, but also accepts `len` itself: the place to insert at
+/// the end, or the end of a slice.
+pub fn boundary(index: i64, len: usize) -> Result<usize, String> {
+    usize::try_from(index)
+        .ok()
+        .filter(|&i| i <= len)
+        .ok_or_else(|| out_of_range(index, len))
+}
+
+pub fn out_of_range(index: i64, len: usize) -> String {
+    format!("Index {index} is out of range for length {len}.")
+}
+
+pub fn invalid_key(key: impl Display) -> String {
+    format!("Map keys must be strings, numbers, booleans or nil, got {key}.")
+}
+
+pub fn undefined_key(key: impl Display) -> String {
+    format!("Undefined key '{key}'.")
+}
@@ -1,3 +1,12 @@
+/// How deeply Lox calls may nest before they fail with a stack overflow,
+/// unless configured otherwise.
+pub const MAX_CALL_DEPTH: usize = 1024;
+
+/// How many bytes of native stack nested Lox calls may use before they fail
+/// with a stack overflow, unless configured otherwise. Spawned Rust threads
+/// get 2 MiB, so this leaves room for the embedder's own frames.
+pub const MAX_STACK_USE: usize = 1024 * 1024;
+
 #[allow(dead_code)]
 pub enum ExitCode {
     Success = 0,
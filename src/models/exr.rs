@@ -1,18 +1,48 @@
 use crate::errors::RuntimeError;
 use crate::models::literals::Literal;
+use crate::models::params::Param;
+use crate::models::stmt::Stmt;
 use crate::models::tokens::Token;
 use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
     Literal(Literal),
     Logical(Box<Expr>, Token, Box<Expr>),
     Binary(Box<Expr>, Token, Box<Expr>),
-    Call(Box<Expr>, Token, Vec<Expr>),
+    /// The callee, the closing parenthesis, the positional arguments and the
+    /// `name: value` arguments that follow them.
+    Call(Box<Expr>, Token, Vec<Expr>, Vec<(Token, Expr)>),
     Unary(Token, Box<Expr>),
     Grouping(Box<Expr>),
-    Variable(Token),
-    Assign(Token, Box<Expr>),
+    Variable(Token, usize),
+    Assign(Token, Box<Expr>, usize),
+    Get(Box<Expr>, Token),
+    Set(Box<Expr>, Token, Box<Expr>),
+    /// A `[a, b, c]` literal, with its opening bracket.
+    List(Token, Vec<Expr>),
+    /// A `"a${b}c"` string, with its first token and its parts in order,
+    /// each of which is turned into text.
+    Interpolation(Token, Vec<Expr>),
+    /// A `{key: value}` literal, with its opening brace.
+    Map(Token, Vec<(Expr, Expr)>),
+    /// `object[index]`, with the opening bracket.
+    Index(Box<Expr>, Token, Box<Expr>),
+    /// `object[index] = value`.
+    SetIndex(Box<Expr>, Token, Box<Expr>, Box<Expr>),
+    /// `target op= value`, `++target` or `target++`, where the target is a
+    /// `Variable`, a `Get` or an `Index`. The flag marks the postfix forms,
+    /// which produce the value from before the update.
+    Compound(Box<Expr>, Token, Box<Expr>, bool),
+    /// `fun (params) { body }` or `(params) => expr`, whose body is then a
+    /// single return. The name is made up from the line, as `anonymous@3`.
+    Lambda(Token, Vec<Param>, Rc<[Stmt]>),
+    This(Token, usize),
+    Super(Token, Token, usize),
 }
 
 impl Display for Expr {
@@ -25,13 +55,31 @@ impl Display for Expr {
             Expr::Binary(left, operator, right) => {
                 write!(f, "({} {} {})", left, operator.lexeme, right)
             }
-            Expr::Call(callee, _paren, arguments) => {
-                write!(f, "func {}({:?})", callee, arguments)
+            Expr::Call(callee, _paren, arguments, named) => {
+                write!(f, "func {}({:?}, {:?})", callee, arguments, named)
             }
             Expr::Unary(operator, right) => write!(f, "({} {})", operator.lexeme, right),
             Expr::Grouping(expression) => write!(f, "(group {})", expression),
-            Expr::Variable(token) => write!(f, "variable {}", token.lexeme),
-            Expr::Assign(token, expr) => write!(f, "assign {} = {}", token.lexeme, expr),
+            Expr::Variable(token, _) => write!(f, "variable {}", token.lexeme),
+            Expr::Assign(token, expr, _) => write!(f, "assign {} = {}", token.lexeme, expr),
+            Expr::Get(object, name) => write!(f, "get {}.{}", object, name.lexeme),
+            Expr::Set(object, name, value) => {
+                write!(f, "set {}.{} = {}", object, name.lexeme, value)
+            }
+            Expr::List(_, elements) => write!(f, "list {:?}", elements),
+            Expr::Interpolation(_, parts) => write!(f, "interpolation {:?}", parts),
+            Expr::Map(_, entries) => write!(f, "map {:?}", entries),
+            Expr::Index(object, _, index) => write!(f, "index {}[{}]", object, index),
+            Expr::SetIndex(object, _, index, value) => {
+                write!(f, "set {}[{}] = {}", object, index, value)
+            }
+            Expr::Compound(target, operator, value, postfix) => {
+                let kind = if *postfix { "postfix" } else { "compound" };
+                write!(f, "{} {} {}= {}", kind, target, operator.lexeme, value)
+            }
+            Expr::Lambda(name, params, _) => write!(f, "fun {}({:?})", name.lexeme, params),
+            Expr::This(_, _) => write!(f, "this"),
+            Expr::Super(_, method, _) => write!(f, "super.{}", method.lexeme),
         }
     }
 }
@@ -54,15 +102,77 @@ pub trait ExprVisitor<T> {
         &mut self,
         callee: &Expr,
         paren: &Token,
-        arguments: &Vec<Expr>,
+        arguments: &[Expr],
+        named: &[(Token, Expr)],
     ) -> Result<T, RuntimeError>;
     fn visit_grouping_expr(&mut self, expression: &Expr) -> Result<T, RuntimeError>;
     fn visit_unary_expr(&mut self, operator: &Token, right: &Expr) -> Result<T, RuntimeError>;
-    fn visit_variable_expr(&mut self, token: &Token) -> Result<T, RuntimeError>;
-    fn visit_assign_expr(&mut self, token: &Token, expr: &Expr) -> Result<T, RuntimeError>;
+    fn visit_variable_expr(&mut self, token: &Token, id: usize) -> Result<T, RuntimeError>;
+    fn visit_assign_expr(
+        &mut self,
+        token: &Token,
+        expr: &Expr,
+        id: usize,
+    ) -> Result<T, RuntimeError>;
+    fn visit_get_expr(&mut self, object: &Expr, name: &Token) -> Result<T, RuntimeError>;
+    fn visit_set_expr(
+        &mut self,
+        object: &Expr,
+        name: &Token,
+        value: &Expr,
+    ) -> Result<T, RuntimeError>;
+    fn visit_list_expr(&mut self, bracket: &Token, elements: &[Expr]) -> Result<T, RuntimeError>;
+    fn visit_interpolation_expr(
+        &mut self,
+        token: &Token,
+        parts: &[Expr],
+    ) -> Result<T, RuntimeError>;
+    fn visit_map_expr(
+        &mut self,
+        brace: &Token,
+        entries: &[(Expr, Expr)],
+    ) -> Result<T, RuntimeError>;
+    fn visit_index_expr(
+        &mut self,
+        object: &Expr,
+        bracket: &Token,
+        index: &Expr,
+    ) -> Result<T, RuntimeError>;
+    fn visit_set_index_expr(
+        &mut self,
+        object: &Expr,
+        bracket: &Token,
+        index: &Expr,
+        value: &Expr,
+    ) -> Result<T, RuntimeError>;
+    fn visit_compound_expr(
+        &mut self,
+        target: &Expr,
+        operator: &Token,
+        value: &Expr,
+        postfix: bool,
+    ) -> Result<T, RuntimeError>;
+    fn visit_lambda_expr(
+        &mut self,
+        name: &Token,
+        params: &[Param],
+        body: &Rc<[Stmt]>,
+    ) -> Result<T, RuntimeError>;
+    fn visit_this_expr(&mut self, keyword: &Token, id: usize) -> Result<T, RuntimeError>;
+    fn visit_super_expr(
+        &mut self,
+        keyword: &Token,
+        method: &Token,
+        id: usize,
+    ) -> Result<T, RuntimeError>;
 }
 
 impl Expr {
+    /// Unique id for variable expressions, used by the resolver to record scope depths.
+    pub fn next_id() -> usize {
+        NEXT_ID.fetch_add(1, Ordering::Relaxed)
+    }
+
     pub fn accept<T>(&self, visitor: &mut dyn ExprVisitor<T>) -> Result<T, RuntimeError> {
         match self {
             Expr::Literal(literal) => visitor.visit_literal_expr(literal),
@@ -70,13 +180,28 @@ impl Expr {
                 visitor.visit_logical_expr(left, operator, right)
             }
             Expr::Binary(left, operator, right) => visitor.visit_binary_expr(left, operator, right),
-            Expr::Call(callee, paren, arguments) => {
-                visitor.visit_call_expr(callee, paren, arguments)
+            Expr::Call(callee, paren, arguments, named) => {
+                visitor.visit_call_expr(callee, paren, arguments, named)
             }
             Expr::Grouping(expression) => visitor.visit_grouping_expr(expression),
             Expr::Unary(operator, right) => visitor.visit_unary_expr(operator, right),
-            Expr::Variable(token) => visitor.visit_variable_expr(token),
-            Expr::Assign(token, expr) => visitor.visit_assign_expr(token, expr),
+            Expr::Variable(token, id) => visitor.visit_variable_expr(token, *id),
+            Expr::Assign(token, expr, id) => visitor.visit_assign_expr(token, expr, *id),
+            Expr::Get(object, name) => visitor.visit_get_expr(object, name),
+            Expr::Set(object, name, value) => visitor.visit_set_expr(object, name, value),
+            Expr::List(bracket, elements) => visitor.visit_list_expr(bracket, elements),
+            Expr::Interpolation(token, parts) => visitor.visit_interpolation_expr(token, parts),
+            Expr::Map(brace, entries) => visitor.visit_map_expr(brace, entries),
+            Expr::Index(object, bracket, index) => visitor.visit_index_expr(object, bracket, index),
+            Expr::SetIndex(object, bracket, index, value) => {
+                visitor.visit_set_index_expr(object, bracket, index, value)
+            }
+            Expr::Compound(target, operator, value, postfix) => {
+                visitor.visit_compound_expr(target, operator, value, *postfix)
+            }
+            Expr::Lambda(name, params, body) => visitor.visit_lambda_expr(name, params, body),
+            Expr::This(keyword, id) => visitor.visit_this_expr(keyword, *id),
+            Expr::Super(keyword, method, id) => visitor.visit_super_expr(keyword, method, *id),
         }
     }
 }
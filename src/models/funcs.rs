@@ -1,25 +1,36 @@
 use crate::environment::Environment;
 use crate::errors::RuntimeError;
 use crate::interpreter::Interpreter;
+use crate::models::classes::Instance;
 use crate::models::literals::Literal;
+use crate::models::params::{self, Binding, Param, Signature};
 use crate::models::stmt::Stmt;
-use crate::models::tokens::Token;
+use crate::models::tokens::Span;
 use std::cell::RefCell;
-use std::fmt::{Display, Formatter};
+use std::fmt::{Debug, Display, Formatter};
 use std::rc::Rc;
 
-#[derive(Debug, Clone, PartialEq)]
+/// Arguments passed by name, in call order.
+pub type NamedArgs = Vec<(String, Literal)>;
+
+/// The body of a native function. It gets the running interpreter so it can
+/// call back into Lox, and may capture any Rust state it needs.
+pub type NativeFn = Rc<dyn Fn(&mut Interpreter, Vec<Literal>) -> Result<Literal, RuntimeError>>;
+
+#[derive(Clone)]
 pub enum Function {
     Native {
         name: String,
         arity: usize,
-        body: fn(Vec<Literal>) -> Result<Literal, RuntimeError>,
+        body: NativeFn,
     },
     Lox {
         name: String,
-        params: Vec<Token>,
-        body: Vec<Stmt>,
+        params: Rc<[Param]>,
+        signature: Signature,
+        body: Rc<[Stmt]>,
         closure: Rc<RefCell<Environment>>,
+        is_initializer: bool,
     },
 }
 
@@ -32,11 +43,86 @@ impl Display for Function {
     }
 }
 
+impl Debug for Function {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Function::Native { name, arity, .. } => f
+                .debug_struct("Native")
+                .field("name", name)
+                .field("arity", arity)
+                .finish_non_exhaustive(),
+            Function::Lox {
+                name,
+                params,
+                body,
+                is_initializer,
+                ..
+            } => f
+                .debug_struct("Lox")
+                .field("name", name)
+                .field("params", params)
+                .field("body", body)
+                .field("is_initializer", is_initializer)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Function::Native { name: a, .. }, Function::Native { name: b, .. }) => a == b,
+            (
+                Function::Lox {
+                    name: a,
+                    params: a_params,
+                    closure: a_closure,
+                    ..
+                },
+                Function::Lox {
+                    name: b,
+                    params: b_params,
+                    closure: b_closure,
+                    ..
+                },
+            ) => a == b && a_params == b_params && Rc::ptr_eq(a_closure, b_closure),
+            _ => false,
+        }
+    }
+}
+
 impl Function {
+    /// The fewest arguments the function can be called with.
     pub fn arity(&self) -> usize {
         match self {
             Function::Native { arity, .. } => *arity,
-            Function::Lox { params, .. } => params.len(),
+            Function::Lox { signature, .. } => signature.required(),
+        }
+    }
+
+    pub fn bind(&self, instance: Rc<RefCell<Instance>>) -> Function {
+        match self {
+            Function::Native { .. } => self.clone(),
+            Function::Lox {
+                name,
+                params,
+                signature,
+                body,
+                closure,
+                is_initializer,
+            } => {
+                let mut env = Environment::new_with_enclosing(closure);
+                env.define("this".to_string(), Literal::Instance(instance));
+
+                Function::Lox {
+                    name: name.clone(),
+                    params: Rc::clone(params),
+                    signature: signature.clone(),
+                    body: Rc::clone(body),
+                    closure: Rc::new(RefCell::new(env)),
+                    is_initializer: *is_initializer,
+                }
+            }
         }
     }
 
@@ -44,36 +130,99 @@ impl Function {
         &self,
         interpreter: &mut Interpreter,
         args: Vec<Literal>,
+    ) -> Result<Literal, RuntimeError> {
+        self.call_with(interpreter, args, Vec::new())
+    }
+
+    /// Calls the function with positional arguments followed by named ones.
+    /// Missing parameters get their defaults, evaluated in order in the new
+    /// call's environment so that they can refer to earlier parameters.
+    ///
+    /// Calls the body makes in tail position come back here as
+    /// `RuntimeError::TailCall` and are made in a loop, so tail recursion
+    /// runs in constant Rust stack space.
+    pub fn call_with(
+        &self,
+        interpreter: &mut Interpreter,
+        args: Vec<Literal>,
+        named: NamedArgs,
+    ) -> Result<Literal, RuntimeError> {
+        let mut result = self.call_once(interpreter, args, named);
+        loop {
+            let Err(RuntimeError::TailCall(span, callee, args, named)) = result else {
+                return result;
+            };
+            result = match &callee {
+                Literal::Callable(function) => function.call_once(interpreter, args, named),
+                _ => interpreter.call_value(&callee, args, named, span),
+            }
+            .map_err(|error| error.or_at(span));
+        }
+    }
+
+    fn call_once(
+        &self,
+        interpreter: &mut Interpreter,
+        args: Vec<Literal>,
+        named: NamedArgs,
     ) -> Result<Literal, RuntimeError> {
         match self {
-            Function::Native { body, .. } => body(args),
+            Function::Native { arity, body, .. } => {
+                if let Some((name, _)) = named.first() {
+                    return Err(RuntimeError::TypeError(
+                        Span::default(),
+                        format!("Unknown parameter '{name}'."),
+                    ));
+                }
+                if args.len() != *arity {
+                    return Err(RuntimeError::TypeError(
+                        Span::default(),
+                        params::arity_error(*arity, Some(*arity), args.len()),
+                    ));
+                }
+                body(interpreter, args)
+            }
             Function::Lox {
+                name,
                 params,
+                signature,
                 body,
                 closure,
-                ..
+                is_initializer,
             } => {
-                if args.len() != params.len() {
-                    return Err(RuntimeError::TypeError(
-                        0,
-                        format!("Expected {} args, got {}", params.len(), args.len()),
-                    ));
-                }
+                let Binding { args, rest } = signature
+                    .bind(args, named)
+                    .map_err(|message| RuntimeError::TypeError(Span::default(), message))?;
 
-                let env = Rc::new(RefCell::new(Environment::new_with_enclosing(closure)));
+                interpreter.in_call(name, |interpreter| {
+                    let env = Rc::new(RefCell::new(Environment::new_with_enclosing(closure)));
 
-                {
-                    let mut env_borrow = env.borrow_mut();
                     for (param, arg) in params.iter().zip(args) {
-                        env_borrow.define(param.lexeme.clone(), arg);
+                        let value = match (arg, &param.default) {
+                            (None, Some(default)) => {
+                                interpreter.evaluate_in(default, Rc::clone(&env))?
+                            }
+                            (arg, _) => arg.unwrap_or(Literal::Nil),
+                        };
+                        env.borrow_mut().define(param.name.lexeme.clone(), value);
+                    }
+                    if let Some(param) = params.last().filter(|param| param.rest) {
+                        env.borrow_mut()
+                            .define(param.name.lexeme.clone(), Literal::list(rest));
                     }
-                }
 
-                match interpreter.execute_block(body, env) {
-                    Ok(()) => Ok(Literal::Nil),
-                    Err(RuntimeError::Return(_, value)) => Ok(value),
-                    Err(e) => Err(e),
-                }
+                    let result = match interpreter.execute_block(body, env) {
+                        Ok(()) => Literal::Nil,
+                        Err(RuntimeError::Return(_, value)) => value,
+                        Err(e) => return Err(e),
+                    };
+
+                    if *is_initializer {
+                        return Ok(closure.borrow().get_value("this").unwrap_or(Literal::Nil));
+                    }
+
+                    Ok(result)
+                })
             }
         }
     }
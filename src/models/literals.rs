@@ -1,23 +1,228 @@
+use crate::models::classes::{Class, Instance};
 use crate::models::funcs::Function;
+use crate::models::natives::NativeValue;
+use crate::models::numbers::{self, Number};
+use indexmap::IndexMap;
+use std::cell::RefCell;
+use std::cmp::Ordering;
 use std::fmt::Display;
+use std::hash::{Hash, Hasher};
+use std::mem;
+use std::ops::Range;
+use std::rc::Rc;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub enum Literal {
     Nil,
     String(String),
+    Integer(i64),
     Number(f64),
     Boolean(bool),
-    Callable(Function),
+    Callable(Rc<Function>),
+    Class(Rc<Class>),
+    Instance(Rc<RefCell<Instance>>),
+    /// Lists are shared: copies of the value alias the same elements.
+    List(Rc<RefCell<Vec<Literal>>>),
+    /// Maps are shared like lists and keep their keys in insertion order.
+    Map(Rc<RefCell<IndexMap<Literal, Literal>>>),
+    /// A lazy range of integers from `range`, walked by for-in loops.
+    Range(Range<i64>),
 }
 
 impl Display for Literal {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_within(f, &mut Vec::new())
+    }
+}
+
+impl Literal {
+    /// Formats the value as an element of the lists and maps in `open`, so
+    /// one that contains itself prints as `[...]` or `{...}` the second time.
+    fn fmt_within(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        open: &mut Vec<*const ()>,
+    ) -> std::fmt::Result {
         match self {
             Literal::Nil => write!(f, "nil"),
             Literal::String(s) => write!(f, "{}", s),
-            Literal::Number(n) => write!(f, "{}", n),
+            Literal::Integer(n) => write!(f, "{}", n),
+            // Debug keeps the fraction of integral floats (`1.0`) and uses
+            // exponents for very large or small ones.
+            Literal::Number(n) => write!(f, "{:?}", n),
             Literal::Boolean(b) => write!(f, "{}", b),
             Literal::Callable(func) => write!(f, "{}", func),
+            Literal::Class(class) => write!(f, "{}", class),
+            Literal::Instance(instance) => write!(f, "{}", instance.borrow()),
+            Literal::List(elements) => {
+                let ptr = Rc::as_ptr(elements) as *const ();
+                if open.contains(&ptr) {
+                    return write!(f, "[...]");
+                }
+                open.push(ptr);
+                write!(f, "[")?;
+                for (i, element) in elements.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    element.fmt_nested(f, open)?;
+                }
+                open.pop();
+                write!(f, "]")
+            }
+            Literal::Map(entries) => {
+                let ptr = Rc::as_ptr(entries) as *const ();
+                if open.contains(&ptr) {
+                    return write!(f, "{{...}}");
+                }
+                open.push(ptr);
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    key.fmt_nested(f, open)?;
+                    write!(f, ": ")?;
+                    value.fmt_nested(f, open)?;
+                }
+                open.pop();
+                write!(f, "}}")
+            }
+            Literal::Range(range) => write!(f, "<range {}..{}>", range.start, range.end),
+        }
+    }
+}
+
+impl PartialEq for Literal {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Literal::Nil, Literal::Nil) => true,
+            (Literal::String(a), Literal::String(b)) => a == b,
+            (Literal::Boolean(a), Literal::Boolean(b)) => a == b,
+            (Literal::Callable(a), Literal::Callable(b)) => a == b,
+            (Literal::Class(a), Literal::Class(b)) => a == b,
+            (Literal::Instance(a), Literal::Instance(b)) => a == b,
+            (Literal::List(a), Literal::List(b)) => Rc::ptr_eq(a, b),
+            (Literal::Map(a), Literal::Map(b)) => Rc::ptr_eq(a, b),
+            (Literal::Range(a), Literal::Range(b)) => a == b,
+            // Integers and floats compare by value, so `1 == 1.0`.
+            _ => match (self.as_number(), other.as_number()) {
+                (Some(a), Some(b)) => numbers::compare(a, b) == Some(Ordering::Equal),
+                _ => false,
+            },
+        }
+    }
+}
+
+// Strictly, NaN breaks reflexivity; a NaN key can be stored but never found
+// again, as with IEEE comparison everywhere else.
+impl Eq for Literal {}
+
+/// Hashing agrees with `PartialEq`, so `m[1]` and `m[1.0]` find the same
+/// entry. Values that can't be map keys only hash their variant.
+impl Hash for Literal {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Literal::String(s) => s.hash(state),
+            Literal::Boolean(b) => b.hash(state),
+            Literal::Integer(n) => numbers::hash(Number::Integer(*n), state),
+            Literal::Number(n) => numbers::hash(Number::Float(*n), state),
+            _ => mem::discriminant(self).hash(state),
+        }
+    }
+}
+
+impl Literal {
+    pub fn list(elements: Vec<Literal>) -> Self {
+        Literal::List(Rc::new(RefCell::new(elements)))
+    }
+
+    pub fn map(entries: IndexMap<Literal, Literal>) -> Self {
+        Literal::Map(Rc::new(RefCell::new(entries)))
+    }
+
+    /// Only immutable values can be map keys.
+    pub fn is_key(&self) -> bool {
+        matches!(
+            self,
+            Literal::Nil
+                | Literal::Boolean(_)
+                | Literal::Integer(_)
+                | Literal::Number(_)
+                | Literal::String(_)
+        )
+    }
+
+    /// Formats an element of a list or map, quoting strings so `["1"]` and
+    /// `[1]` print differently.
+    fn fmt_nested(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        open: &mut Vec<*const ()>,
+    ) -> std::fmt::Result {
+        match self {
+            Literal::String(s) => write!(f, "\"{}\"", s),
+            _ => self.fmt_within(f, open),
+        }
+    }
+
+    pub fn as_number(&self) -> Option<Number> {
+        match self {
+            Literal::Integer(n) => Some(Number::Integer(*n)),
+            Literal::Number(n) => Some(Number::Float(*n)),
+            _ => None,
+        }
+    }
+}
+
+impl NativeValue for Literal {
+    fn nil() -> Self {
+        Literal::Nil
+    }
+
+    fn boolean(b: bool) -> Self {
+        Literal::Boolean(b)
+    }
+
+    fn new_list(elements: Vec<Self>) -> Self {
+        Literal::list(elements)
+    }
+
+    fn new_range(range: Range<i64>) -> Self {
+        Literal::Range(range)
+    }
+
+    fn number(&self) -> Option<Number> {
+        self.as_number()
+    }
+
+    fn string(&self) -> Option<&str> {
+        match self {
+            Literal::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn list(&self) -> Option<&Rc<RefCell<Vec<Self>>>> {
+        match self {
+            Literal::List(elements) => Some(elements),
+            _ => None,
+        }
+    }
+
+    fn map(&self) -> Option<&Rc<RefCell<IndexMap<Self, Self>>>> {
+        match self {
+            Literal::Map(entries) => Some(entries),
+            _ => None,
+        }
+    }
+}
+
+impl From<Number> for Literal {
+    fn from(number: Number) -> Self {
+        match number {
+            Number::Integer(n) => Literal::Integer(n),
+            Number::Float(n) => Literal::Number(n),
         }
     }
 }
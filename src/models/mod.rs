@@ -1,7 +1,13 @@
+pub mod classes;
+pub mod collections;
 pub mod constants;
 pub mod exr;
 pub mod funcs;
 pub mod literals;
+pub mod natives;
+pub mod numbers;
+pub mod params;
+pub mod stack;
 pub mod stmt;
 pub mod token_type;
 pub mod tokens;
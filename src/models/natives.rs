@@ -0,0 +1,184 @@
+//! The built-in native functions, generic over each backend's values.
+
+use crate::models::collections;
+use crate::models::numbers::{self, Number};
+use indexmap::IndexMap;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::fmt::Display;
+use std::hash::Hash;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// What the built-in natives need to know about a backend's values.
+pub trait NativeValue: Clone + Display + Eq + Hash + From<Number> {
+    fn nil() -> Self;
+    fn boolean(b: bool) -> Self;
+    fn new_list(elements: Vec<Self>) -> Self;
+    fn new_range(range: std::ops::Range<i64>) -> Self;
+    fn number(&self) -> Option<Number>;
+    fn string(&self) -> Option<&str>;
+    fn list(&self) -> Option<&Rc<RefCell<Vec<Self>>>>;
+    fn map(&self) -> Option<&Rc<RefCell<IndexMap<Self, Self>>>>;
+}
+
+/// The body of a built-in native. Errors are messages for the backend to
+/// raise as type errors at the call.
+pub type NativeBody<V> = fn(&[V]) -> Result<V, String>;
+
+/// Every built-in native, as its name, arity and body.
+pub fn builtins<V: NativeValue>() -> Vec<(&'static str, usize, NativeBody<V>)> {
+    vec![
+        ("clock", 0, clock),
+        ("int", 1, int),
+        ("float", 1, float),
+        ("len", 1, len),
+        ("push", 2, push),
+        ("pop", 1, pop),
+        ("insert", 3, insert),
+        ("remove", 2, remove),
+        ("slice", 3, slice),
+        ("sort", 1, sort),
+        ("has", 2, has),
+        ("delete", 2, delete),
+        ("keys", 1, keys),
+        ("values", 1, values),
+        ("range", 2, range),
+    ]
+}
+
+pub fn list_arg<V: NativeValue>(value: &V) -> Result<&Rc<RefCell<Vec<V>>>, String> {
+    value
+        .list()
+        .ok_or_else(|| format!("Expected a list, got {value}."))
+}
+
+pub fn map_arg<V: NativeValue>(value: &V) -> Result<&Rc<RefCell<IndexMap<V, V>>>, String> {
+    value
+        .map()
+        .ok_or_else(|| format!("Expected a map, got {value}."))
+}
+
+pub fn index_arg<V: NativeValue>(value: &V) -> Result<i64, String> {
+    match value.number() {
+        Some(Number::Integer(n)) => Ok(n),
+        _ => Err(format!("List index must be an integer, got {value}.")),
+    }
+}
+
+fn clock<V: NativeValue>(_args: &[V]) -> Result<V, String> {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs_f64();
+    Ok(Number::Float(seconds).into())
+}
+
+fn int<V: NativeValue>(args: &[V]) -> Result<V, String> {
+    let integer = args[0].number().and_then(numbers::to_integer);
+    integer
+        .map(|n| Number::Integer(n).into())
+        .ok_or_else(|| format!("Can't convert {} to an integer.", args[0]))
+}
+
+fn float<V: NativeValue>(args: &[V]) -> Result<V, String> {
+    match args[0].number() {
+        Some(n) => Ok(Number::Float(n.as_f64()).into()),
+        None => Err(format!("Can't convert {} to a float.", args[0])),
+    }
+}
+
+fn len<V: NativeValue>(args: &[V]) -> Result<V, String> {
+    let value = &args[0];
+    let len = if let Some(elements) = value.list() {
+        elements.borrow().len()
+    } else if let Some(s) = value.string() {
+        s.chars().count()
+    } else if let Some(entries) = value.map() {
+        entries.borrow().len()
+    } else {
+        return Err(format!("Can't take the length of {value}."));
+    };
+    Ok(Number::Integer(len as i64).into())
+}
+
+fn push<V: NativeValue>(args: &[V]) -> Result<V, String> {
+    list_arg(&args[0])?.borrow_mut().push(args[1].clone());
+    Ok(V::nil())
+}
+
+fn pop<V: NativeValue>(args: &[V]) -> Result<V, String> {
+    let popped = list_arg(&args[0])?.borrow_mut().pop();
+    popped.ok_or_else(|| "Can't pop from an empty list.".to_string())
+}
+
+fn insert<V: NativeValue>(args: &[V]) -> Result<V, String> {
+    let mut elements = list_arg(&args[0])?.borrow_mut();
+    let at = collections::boundary(index_arg(&args[1])?, elements.len())?;
+    elements.insert(at, args[2].clone());
+    Ok(V::nil())
+}
+
+fn remove<V: NativeValue>(args: &[V]) -> Result<V, String> {
+    let mut elements = list_arg(&args[0])?.borrow_mut();
+    let at = collections::position(index_arg(&args[1])?, elements.len())?;
+    Ok(elements.remove(at))
+}
+
+fn slice<V: NativeValue>(args: &[V]) -> Result<V, String> {
+    let elements = list_arg(&args[0])?.borrow();
+    let start = collections::boundary(index_arg(&args[1])?, elements.len());
+    let end = collections::boundary(index_arg(&args[2])?, elements.len());
+    let (start, end) = (start?, end?);
+    Ok(V::new_list(elements[start..end.max(start)].to_vec()))
+}
+
+fn sort<V: NativeValue>(args: &[V]) -> Result<V, String> {
+    let mut error = None;
+    list_arg(&args[0])?.borrow_mut().sort_by(|a, b| {
+        sort_order(a, b).unwrap_or_else(|| {
+            error.get_or_insert_with(|| format!("Can't compare {a} and {b}."));
+            Ordering::Equal
+        })
+    });
+    error.map_or(Ok(V::nil()), Err)
+}
+
+/// Orders numbers by value and strings lexicographically; `sort` fails on
+/// anything else.
+fn sort_order<V: NativeValue>(a: &V, b: &V) -> Option<Ordering> {
+    match (a.string(), b.string()) {
+        (Some(a), Some(b)) => Some(a.cmp(b)),
+        _ => Some(numbers::compare(a.number()?, b.number()?).unwrap_or(Ordering::Equal)),
+    }
+}
+
+fn has<V: NativeValue>(args: &[V]) -> Result<V, String> {
+    let found = map_arg(&args[0])?.borrow().contains_key(&args[1]);
+    Ok(V::boolean(found))
+}
+
+fn delete<V: NativeValue>(args: &[V]) -> Result<V, String> {
+    let removed = map_arg(&args[0])?.borrow_mut().shift_remove(&args[1]);
+    Ok(V::boolean(removed.is_some()))
+}
+
+fn keys<V: NativeValue>(args: &[V]) -> Result<V, String> {
+    let keys = map_arg(&args[0])?.borrow().keys().cloned().collect();
+    Ok(V::new_list(keys))
+}
+
+fn values<V: NativeValue>(args: &[V]) -> Result<V, String> {
+    let values = map_arg(&args[0])?.borrow().values().cloned().collect();
+    Ok(V::new_list(values))
+}
+
+fn range<V: NativeValue>(args: &[V]) -> Result<V, String> {
+    match (args[0].number(), args[1].number()) {
+        (Some(Number::Integer(start)), Some(Number::Integer(end))) => Ok(V::new_range(start..end)),
+        _ => Err(format!(
+            "Range bounds must be integers, got {} and {}.",
+            args[0], args[1]
+        )),
+    }
+}
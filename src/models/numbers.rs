@@ -0,0 +1,208 @@
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+
+/// A numeric operand. Integer operations stay integers unless they overflow,
+/// which is an error; mixing an integer with a float gives a float.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Number {
+    Integer(i64),
+    Float(f64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArithmeticOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    IntDivide,
+    Modulo,
+    Power,
+}
+
+/// Operators that only apply to integers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BitwiseOp {
+    And,
+    Or,
+    Xor,
+    ShiftLeft,
+    ShiftRight,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArithmeticError {
+    DivisionByZero,
+    Overflow,
+    InvalidShift,
+}
+
+impl Number {
+    pub fn as_f64(self) -> f64 {
+        match self {
+            Number::Integer(n) => n as f64,
+            Number::Float(n) => n,
+        }
+    }
+}
+
+impl ArithmeticOp {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            ArithmeticOp::Add => "+",
+            ArithmeticOp::Subtract => "-",
+            ArithmeticOp::Multiply => "*",
+            ArithmeticOp::Divide => "/",
+            ArithmeticOp::IntDivide => "~/",
+            ArithmeticOp::Modulo => "%",
+            ArithmeticOp::Power => "**",
+        }
+    }
+
+    pub fn apply(self, left: Number, right: Number) -> Result<Number, ArithmeticError> {
+        match (left, right) {
+            (Number::Integer(l), Number::Integer(r)) => self.apply_integers(l, r),
+            _ => self.apply_floats(left.as_f64(), right.as_f64()),
+        }
+    }
+
+    fn apply_integers(self, l: i64, r: i64) -> Result<Number, ArithmeticError> {
+        if r == 0 && matches!(self, Self::Divide | Self::IntDivide | Self::Modulo) {
+            return Err(ArithmeticError::DivisionByZero);
+        }
+
+        let result = match self {
+            ArithmeticOp::Add => l.checked_add(r),
+            ArithmeticOp::Subtract => l.checked_sub(r),
+            ArithmeticOp::Multiply => l.checked_mul(r),
+            // `/` only stays an integer when the division is exact.
+            ArithmeticOp::Divide if l.wrapping_rem(r) != 0 => {
+                return Ok(Number::Float(l as f64 / r as f64))
+            }
+            ArithmeticOp::Divide | ArithmeticOp::IntDivide => l.checked_div(r),
+            ArithmeticOp::Modulo => l.checked_rem(r),
+            // 0, 1 and -1 stay in range whatever the exponent.
+            ArithmeticOp::Power if r > 0 && (-1..=1).contains(&l) => {
+                Some(if l == -1 && r % 2 == 0 { 1 } else { l })
+            }
+            // A negative exponent makes a fraction.
+            ArithmeticOp::Power => match u32::try_from(r) {
+                Ok(exponent) => l.checked_pow(exponent),
+                Err(_) if r < 0 => return Ok(Number::Float((l as f64).powf(r as f64))),
+                Err(_) => None,
+            },
+        };
+
+        result.map(Number::Integer).ok_or(ArithmeticError::Overflow)
+    }
+
+    fn apply_floats(self, l: f64, r: f64) -> Result<Number, ArithmeticError> {
+        if r == 0.0 && matches!(self, Self::Divide | Self::IntDivide | Self::Modulo) {
+            return Err(ArithmeticError::DivisionByZero);
+        }
+
+        Ok(Number::Float(match self {
+            ArithmeticOp::Add => l + r,
+            ArithmeticOp::Subtract => l - r,
+            ArithmeticOp::Multiply => l * r,
+            ArithmeticOp::Divide => l / r,
+            ArithmeticOp::IntDivide => (l / r).trunc(),
+            ArithmeticOp::Modulo => l % r,
+            ArithmeticOp::Power => l.powf(r),
+        }))
+    }
+}
+
+impl BitwiseOp {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BitwiseOp::And => "&",
+            BitwiseOp::Or => "|",
+            BitwiseOp::Xor => "^",
+            BitwiseOp::ShiftLeft => "<<",
+            BitwiseOp::ShiftRight => ">>",
+        }
+    }
+
+    /// Shifts move bits out without an overflow check, but the shift amount
+    /// must be between 0 and 63. `>>` keeps the sign.
+    pub fn apply(self, l: i64, r: i64) -> Result<i64, ArithmeticError> {
+        let shift = || u32::try_from(r).ok().filter(|&r| r < i64::BITS);
+
+        match self {
+            BitwiseOp::And => Some(l & r),
+            BitwiseOp::Or => Some(l | r),
+            BitwiseOp::Xor => Some(l ^ r),
+            BitwiseOp::ShiftLeft => shift().map(|r| l << r),
+            BitwiseOp::ShiftRight => shift().map(|r| l >> r),
+        }
+        .ok_or(ArithmeticError::InvalidShift)
+    }
+}
+
+/// Negates `n`, failing when an integer has no positive counterpart.
+pub fn negate(n: Number) -> Result<Number, ArithmeticError> {
+    match n {
+        Number::Integer(n) => n
+            .checked_neg()
+            .map(Number::Integer)
+            .ok_or(ArithmeticError::Overflow),
+        Number::Float(n) => Ok(Number::Float(-n)),
+    }
+}
+
+/// Orders two numbers exactly, even an integer and a float that `as_f64`
+/// would round to the same value.
+pub fn compare(left: Number, right: Number) -> Option<Ordering> {
+    match (left, right) {
+        (Number::Integer(l), Number::Integer(r)) => Some(l.cmp(&r)),
+        (Number::Float(l), Number::Float(r)) => l.partial_cmp(&r),
+        (Number::Integer(l), Number::Float(r)) => compare_mixed(l, r),
+        (Number::Float(l), Number::Integer(r)) => compare_mixed(r, l).map(Ordering::reverse),
+    }
+}
+
+fn compare_mixed(integer: i64, float: f64) -> Option<Ordering> {
+    // 2^63, the first float above every integer.
+    const LIMIT: f64 = 9_223_372_036_854_775_808.0;
+
+    if float.is_nan() {
+        return None;
+    }
+    if float >= LIMIT {
+        return Some(Ordering::Less);
+    }
+    if float < -LIMIT {
+        return Some(Ordering::Greater);
+    }
+
+    // The whole part is now exactly an `i64`; the fraction breaks ties.
+    let whole = float.trunc();
+    Some(
+        integer
+            .cmp(&(whole as i64))
+            .then_with(|| whole.partial_cmp(&float).unwrap_or(Ordering::Equal)),
+    )
+}
+
+/// Converts `n` to an integer, truncating floats. Fails for NaN, infinities
+/// and floats outside the `i64` range.
+pub fn to_integer(n: Number) -> Option<i64> {
+    match n {
+        Number::Integer(n) => Some(n),
+        Number::Float(n) => {
+            let n = n.trunc();
+            // `i64::MAX as f64` rounds up to 2^63, which is already too large.
+            (n.is_finite() && n >= i64::MIN as f64 && n < i64::MAX as f64).then_some(n as i64)
+        }
+    }
+}
+
+/// Hashes `n` consistently with `compare`: integers hash as their nearest
+/// float, which is the float they compare equal to if there is one, and
+/// `-0.0` like `0.0`.
+pub fn hash<H: Hasher>(n: Number, state: &mut H) {
+    let n = n.as_f64();
+    let n = if n == 0.0 { 0.0 } else { n };
+    n.to_bits().hash(state);
+}
@@ -0,0 +1,119 @@
+//! Parameter lists with defaults and a rest parameter, and argument binding.
+
+use crate::models::exr::Expr;
+use crate::models::tokens::Token;
+
+/// A function parameter: `name`, `name = default` or `...name`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    pub name: Token,
+    pub default: Option<Expr>,
+    pub rest: bool,
+}
+
+/// The shape of a parameter list. The parser guarantees that parameters
+/// with a default follow the required ones, and that the rest parameter, if
+/// any, comes last.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Signature {
+    // Every parameter but the rest one, in order.
+    names: Vec<String>,
+    required: usize,
+    rest: bool,
+}
+
+/// Arguments matched to a `Signature`: one slot per named parameter, empty
+/// when its default should be used, and the extras for the rest parameter.
+#[derive(Debug)]
+pub struct Binding<T> {
+    pub args: Vec<Option<T>>,
+    pub rest: Vec<T>,
+}
+
+impl Signature {
+    pub fn new(params: &[Param]) -> Self {
+        Self {
+            names: params
+                .iter()
+                .filter(|param| !param.rest)
+                .map(|param| param.name.lexeme.clone())
+                .collect(),
+            required: params
+                .iter()
+                .take_while(|param| param.default.is_none() && !param.rest)
+                .count(),
+            rest: params.iter().any(|param| param.rest),
+        }
+    }
+
+    /// The fewest arguments a call can pass.
+    pub fn required(&self) -> usize {
+        self.required
+    }
+
+    pub fn has_rest(&self) -> bool {
+        self.rest
+    }
+
+    /// Whether every call must pass exactly one argument per parameter.
+    pub fn is_fixed(&self) -> bool {
+        self.required == self.names.len() && !self.rest
+    }
+
+    /// Matches positional arguments to parameters in order, then named ones
+    /// by name. Extra positional arguments go to the rest parameter.
+    pub fn bind<T, S: AsRef<str>>(
+        &self,
+        positional: Vec<T>,
+        named: Vec<(S, T)>,
+    ) -> Result<Binding<T>, String> {
+        let count = positional.len() + named.len();
+        let mut args: Vec<Option<T>> = self.names.iter().map(|_| None).collect();
+        let mut rest = Vec::new();
+
+        for (index, value) in positional.into_iter().enumerate() {
+            match args.get_mut(index) {
+                Some(slot) => *slot = Some(value),
+                None if self.rest => rest.push(value),
+                None => return Err(self.arity_error(count)),
+            }
+        }
+
+        let by_name = !named.is_empty();
+        for (name, value) in named {
+            let name = name.as_ref();
+            let Some(index) = self.names.iter().position(|param| param == name) else {
+                return Err(format!("Unknown parameter '{name}'."));
+            };
+            if args[index].is_some() {
+                return Err(format!("Got more than one value for parameter '{name}'."));
+            }
+            args[index] = Some(value);
+        }
+
+        if let Some(index) = args[..self.required].iter().position(Option::is_none) {
+            return Err(if by_name {
+                format!("Missing argument for parameter '{}'.", self.names[index])
+            } else {
+                self.arity_error(count)
+            });
+        }
+
+        Ok(Binding { args, rest })
+    }
+
+    fn arity_error(&self, count: usize) -> String {
+        let max = (!self.rest).then_some(self.names.len());
+        arity_error(self.required, max, count)
+    }
+}
+
+/// Describes a call that passed `count` arguments to a function accepting
+/// between `min` and `max` of them, with no upper bound when `max` is `None`.
+pub fn arity_error(min: usize, max: Option<usize>, count: usize) -> String {
+    match max {
+        Some(max) if max == min => format!("Expected {min} arguments but got {count}"),
+        Some(max) => format!("Expected {min} to {max} arguments but got {count}"),
+        None => format!("Expected at least {min} arguments but got {count}"),
+    }
+}
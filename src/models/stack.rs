@@ -0,0 +1,36 @@
+//! Tracks how much native stack nested Lox calls have used.
+
+/// The native stack used since the outermost Lox call, compared against a
+/// limit so deep recursion fails before the thread's stack runs out.
+#[derive(Debug)]
+pub struct StackUse {
+    base: usize,
+    limit: usize,
+}
+
+impl StackUse {
+    pub fn new(limit: usize) -> Self {
+        Self { base: 0, limit }
+    }
+
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+    }
+
+    /// Marks the current position as the start of the outermost call.
+    pub fn start(&mut self) {
+        self.base = position();
+    }
+
+    /// Whether the calls since `start` have used more than the limit. Stacks
+    /// grow downwards on every platform Rust supports threads on.
+    pub fn exhausted(&self) -> bool {
+        self.base.saturating_sub(position()) > self.limit
+    }
+}
+
+#[inline(never)]
+fn position() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
+}
@@ -1,16 +1,27 @@
 use crate::models::exr::Expr;
+use crate::models::params::Param;
 use crate::models::tokens::Token;
 use std::fmt::{Display, Formatter, Result};
+use std::rc::Rc;
 
+/// Class, function and variable declarations end with the text of the `///`
+/// doc comment written right before them, if any.
 #[derive(Debug, Clone, PartialEq)]
 pub enum Stmt {
     Expr(Expr),
-    Function(Token, Vec<Token>, Vec<Stmt>),
+    Class(Token, Option<Expr>, Vec<Stmt>, Option<String>),
+    Function(Token, Vec<Param>, Rc<[Stmt]>, Option<String>),
     If(Expr, Box<Stmt>, Option<Box<Stmt>>),
     Print(Expr),
-    While(Expr, Box<Stmt>),
+    /// The condition, the body, and the increment of a desugared `for` loop,
+    /// which also runs after a `continue`.
+    While(Expr, Box<Stmt>, Option<Expr>),
+    /// `for (var name in iterable) body`.
+    ForIn(Token, Expr, Box<Stmt>),
+    Break(Token),
+    Continue(Token),
     Return(Token, Option<Expr>),
-    Var(String, Option<Expr>),
+    Var(Token, Option<Expr>, Option<String>),
     Block(Vec<Stmt>),
 }
 
@@ -18,17 +29,33 @@ impl Display for Stmt {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         match self {
             Stmt::Expr(e) => write!(f, "expr {}", e),
-            Stmt::Function(token, params, body) => {
+            Stmt::Class(name, superclass, methods, _) => match superclass {
+                Some(superclass) => {
+                    write!(f, "class {} < {} {:?}", name.lexeme, superclass, methods)
+                }
+                None => write!(f, "class {} {:?}", name.lexeme, methods),
+            },
+            Stmt::Function(token, params, body, _) => {
                 write!(f, "function {}({:?}, {:?})", token, params, body)
             }
             Stmt::If(c, t, e) => write!(f, "if {} then {} else {:?}", c, t, e),
             Stmt::Print(e) => write!(f, "print {}", e),
-            Stmt::While(condition, body) => write!(f, "while loop {} do {}", condition, body),
-            Stmt::Var(token, expr) => {
+            Stmt::While(condition, body, increment) => match increment {
+                Some(increment) => {
+                    write!(f, "while loop {} do {} then {}", condition, body, increment)
+                }
+                None => write!(f, "while loop {} do {}", condition, body),
+            },
+            Stmt::ForIn(name, iterable, body) => {
+                write!(f, "for {} in {} do {}", name.lexeme, iterable, body)
+            }
+            Stmt::Break(_) => write!(f, "break"),
+            Stmt::Continue(_) => write!(f, "continue"),
+            Stmt::Var(token, expr, _) => {
                 if let Some(expr) = expr {
-                    write!(f, "var {} = {}", token, expr)
+                    write!(f, "var {} = {}", token.lexeme, expr)
                 } else {
-                    write!(f, "var {}", token)
+                    write!(f, "var {}", token.lexeme)
                 }
             }
             Stmt::Return(_token, expr) => {
@@ -43,7 +70,8 @@ impl Display for Stmt {
 
 pub trait StmtVisitor<T> {
     fn visit_expr_stmt(&mut self, expr: &Expr) -> T;
-    fn visit_function_stmt(&mut self, name: &Token, params: &Vec<Token>, body: &Vec<Stmt>) -> T;
+    fn visit_class_stmt(&mut self, name: &Token, superclass: &Option<Expr>, methods: &[Stmt]) -> T;
+    fn visit_function_stmt(&mut self, name: &Token, params: &[Param], body: &Rc<[Stmt]>) -> T;
     fn visit_if_stmt(
         &mut self,
         cond: &Expr,
@@ -51,22 +79,41 @@ pub trait StmtVisitor<T> {
         else_branch: &Option<Box<Stmt>>,
     ) -> T;
     fn visit_print_stmt(&mut self, expr: &Expr) -> T;
-    fn visit_while_stmt(&mut self, cond: &Expr, body: &Stmt) -> T;
+    fn visit_while_stmt(&mut self, cond: &Expr, body: &Stmt, increment: &Option<Expr>) -> T;
+    fn visit_for_in_stmt(&mut self, name: &Token, iterable: &Expr, body: &Stmt) -> T;
+    fn visit_break_stmt(&mut self, keyword: &Token) -> T;
+    fn visit_continue_stmt(&mut self, keyword: &Token) -> T;
     fn visit_return_stmt(&mut self, token: &Token, expr: &Option<Expr>) -> T;
-    fn visit_var_stmt(&mut self, lexeme: String, expr: &Option<Expr>) -> T;
-    fn visit_block_stmt(&mut self, stmts: &Vec<Stmt>) -> T;
+    fn visit_var_stmt(&mut self, name: &Token, expr: &Option<Expr>) -> T;
+    fn visit_block_stmt(&mut self, stmts: &[Stmt]) -> T;
 }
 
 impl Stmt {
+    /// The doc comment of a class, function or variable declaration.
+    pub fn doc(&self) -> Option<&str> {
+        match self {
+            Stmt::Class(.., doc) | Stmt::Function(.., doc) | Stmt::Var(.., doc) => doc.as_deref(),
+            _ => None,
+        }
+    }
+
     pub fn accept<T>(&self, visitor: &mut dyn StmtVisitor<T>) -> T {
         match self {
             Stmt::Expr(expr) => visitor.visit_expr_stmt(expr),
-            Stmt::Function(name, params, body) => visitor.visit_function_stmt(name, params, body),
+            Stmt::Class(name, superclass, methods, _) => {
+                visitor.visit_class_stmt(name, superclass, methods)
+            }
+            Stmt::Function(name, params, body, _) => {
+                visitor.visit_function_stmt(name, params, body)
+            }
             Stmt::If(cond, then_b, else_b) => visitor.visit_if_stmt(cond, then_b, else_b),
             Stmt::Print(expr) => visitor.visit_print_stmt(expr),
-            Stmt::While(cond, body) => visitor.visit_while_stmt(cond, body),
+            Stmt::While(cond, body, increment) => visitor.visit_while_stmt(cond, body, increment),
+            Stmt::ForIn(name, iterable, body) => visitor.visit_for_in_stmt(name, iterable, body),
+            Stmt::Break(keyword) => visitor.visit_break_stmt(keyword),
+            Stmt::Continue(keyword) => visitor.visit_continue_stmt(keyword),
             Stmt::Return(token, expr) => visitor.visit_return_stmt(token, expr),
-            Stmt::Var(lexeme, expr) => visitor.visit_var_stmt(lexeme.clone(), expr),
+            Stmt::Var(name, expr, _) => visitor.visit_var_stmt(name, expr),
             Stmt::Block(stmts) => visitor.visit_block_stmt(stmts),
         }
     }
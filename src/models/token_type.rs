@@ -6,12 +6,15 @@ lazy_static! {
     pub static ref KEYWORDS: HashMap<&'static str, TokenType> = {
         let mut map = HashMap::new();
         map.insert("and", TokenType::And);
+        map.insert("break", TokenType::Break);
         map.insert("class", TokenType::Class);
+        map.insert("continue", TokenType::Continue);
         map.insert("else", TokenType::Else);
         map.insert("false", TokenType::False);
         map.insert("for", TokenType::For);
         map.insert("fun", TokenType::Fun);
         map.insert("if", TokenType::If);
+        map.insert("in", TokenType::In);
         map.insert("nil", TokenType::Nil);
         map.insert("or", TokenType::Or);
         map.insert("print", TokenType::Print);
@@ -32,37 +35,65 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
+    Colon,
     Dot,
     Minus,
     Plus,
     Semicolon,
     Slash,
     Star,
+    Percent,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
 
-    // One or two character tokens.
+    // One, two or three character tokens.
     Bang,
     BangEqual,
     Equal,
     EqualEqual,
+    EqualGreater,
     Greater,
     GreaterEqual,
+    GreaterGreater,
     Less,
     LessEqual,
+    LessLess,
+    TildeSlash,
+    StarStar,
+    PlusPlus,
+    MinusMinus,
+    PlusEqual,
+    MinusEqual,
+    StarEqual,
+    SlashEqual,
+    PercentEqual,
+    DotDotDot,
 
     // Literals.
     Identifier,
     String,
+    // The part of a string literal before a `${`.
+    Interpolation,
     Number,
+    // A `///` comment, which documents the declaration after it.
+    DocComment,
 
     // Keywords.
     And,
+    Break,
     Class,
+    Continue,
     Else,
     False,
     Fun,
     For,
     If,
+    In,
     Nil,
     Or,
     Print,
@@ -78,42 +109,68 @@ pub enum TokenType {
 
 impl Display for TokenType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.to_string())
+        write!(f, "{}", self.as_str())
     }
 }
 
 impl TokenType {
-    fn to_string(&self) -> &str {
+    fn as_str(self) -> &'static str {
         match self {
             TokenType::RightParen => "RIGHT_PAREN",
             TokenType::LeftParen => "LEFT_PAREN",
             TokenType::RightBrace => "RIGHT_BRACE",
             TokenType::LeftBrace => "LEFT_BRACE",
+            TokenType::LeftBracket => "LEFT_BRACKET",
+            TokenType::RightBracket => "RIGHT_BRACKET",
             TokenType::Comma => "COMMA",
+            TokenType::Colon => "COLON",
             TokenType::Dot => "DOT",
             TokenType::Minus => "MINUS",
             TokenType::Plus => "PLUS",
             TokenType::Semicolon => "SEMICOLON",
             TokenType::Star => "STAR",
             TokenType::Slash => "SLASH",
+            TokenType::Percent => "PERCENT",
+            TokenType::Ampersand => "AMPERSAND",
+            TokenType::Pipe => "PIPE",
+            TokenType::Caret => "CARET",
+            TokenType::Tilde => "TILDE",
+            TokenType::GreaterGreater => "GREATER_GREATER",
+            TokenType::LessLess => "LESS_LESS",
+            TokenType::TildeSlash => "TILDE_SLASH",
+            TokenType::StarStar => "STAR_STAR",
+            TokenType::PlusPlus => "PLUS_PLUS",
+            TokenType::MinusMinus => "MINUS_MINUS",
+            TokenType::PlusEqual => "PLUS_EQUAL",
+            TokenType::MinusEqual => "MINUS_EQUAL",
+            TokenType::StarEqual => "STAR_EQUAL",
+            TokenType::SlashEqual => "SLASH_EQUAL",
+            TokenType::PercentEqual => "PERCENT_EQUAL",
+            TokenType::DotDotDot => "DOT_DOT_DOT",
             TokenType::Bang => "BANG",
             TokenType::BangEqual => "BANG_EQUAL",
             TokenType::Equal => "EQUAL",
             TokenType::EqualEqual => "EQUAL_EQUAL",
+            TokenType::EqualGreater => "EQUAL_GREATER",
             TokenType::Greater => "GREATER",
             TokenType::GreaterEqual => "GREATER_EQUAL",
             TokenType::Less => "LESS",
             TokenType::LessEqual => "LESS_EQUAL",
             TokenType::String => "STRING",
+            TokenType::Interpolation => "INTERPOLATION",
             TokenType::Number => "NUMBER",
+            TokenType::DocComment => "DOC_COMMENT",
             TokenType::Identifier => "IDENTIFIER",
             TokenType::And => "AND",
+            TokenType::Break => "BREAK",
             TokenType::Class => "CLASS",
+            TokenType::Continue => "CONTINUE",
             TokenType::Else => "ELSE",
             TokenType::False => "FALSE",
             TokenType::For => "FOR",
             TokenType::Fun => "FUN",
             TokenType::If => "IF",
+            TokenType::In => "IN",
             TokenType::Nil => "NIL",
             TokenType::Or => "OR",
             TokenType::Return => "RETURN",
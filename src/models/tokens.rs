@@ -2,12 +2,42 @@ use crate::models::literals::Literal;
 use crate::models::token_type::TokenType;
 use std::fmt::Display;
 
+/// Where a piece of source code sits: its 1-based line and column, plus its
+/// byte offset and length. A column of 0 means only the line is known, as for
+/// tokens made up by the interpreter itself.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub offset: usize,
+    pub length: usize,
+}
+
+impl Span {
+    pub fn new(line: usize, column: usize, offset: usize, length: usize) -> Self {
+        Self {
+            line,
+            column,
+            offset,
+            length,
+        }
+    }
+
+    /// A span that only knows its line.
+    pub fn from_line(line: usize) -> Self {
+        Self {
+            line,
+            ..Self::default()
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     pub token_type: TokenType,
     pub lexeme: String,
     pub literal: Literal,
-    pub line: usize,
+    pub span: Span,
 }
 
 impl Display for Token {
@@ -21,12 +51,12 @@ impl Display for Token {
 }
 
 impl Token {
-    pub fn new(token_type: TokenType, lexeme: String, literal: Literal, line: usize) -> Self {
+    pub fn new(token_type: TokenType, lexeme: String, literal: Literal, span: Span) -> Self {
         Self {
             token_type,
             lexeme,
             literal,
-            line,
+            span,
         }
     }
 }
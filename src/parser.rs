@@ -1,23 +1,51 @@
 use crate::errors::ParseError;
 use crate::models::exr::Expr;
 use crate::models::literals::Literal;
+use crate::models::params::Param;
 use crate::models::stmt::Stmt;
 use crate::models::token_type::TokenType;
 use crate::models::tokens::Token;
+use std::collections::HashMap;
+use std::rc::Rc;
 
 #[derive(Debug, Clone)]
 pub struct Parser {
     tokens: Vec<Token>,
+    // Doc comments, keyed by the index of the token they precede.
+    docs: HashMap<usize, String>,
     current: usize,
-    pub errors: Vec<String>,
+    // How many loops enclose the statement being parsed, reset inside
+    // function bodies.
+    loop_depth: usize,
+    pub errors: Vec<ParseError>,
     pub stmts: Vec<Stmt>,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
+        // Doc comments are not part of the grammar: set them aside so they can
+        // be attached to the declaration that follows.
+        let mut docs = HashMap::new();
+        let mut lines: Vec<String> = Vec::new();
+        let mut code = Vec::with_capacity(tokens.len());
+
+        for token in tokens {
+            if token.token_type == TokenType::DocComment {
+                lines.push(token.literal.to_string());
+                continue;
+            }
+            if !lines.is_empty() {
+                docs.insert(code.len(), lines.join("\n"));
+                lines.clear();
+            }
+            code.push(token);
+        }
+
         Self {
-            tokens,
+            tokens: code,
+            docs,
             current: 0,
+            loop_depth: 0,
             errors: Vec::new(),
             stmts: Vec::new(),
         }
@@ -30,7 +58,7 @@ impl Parser {
                     self.stmts.push(stmt);
                 }
                 Err(error) => {
-                    self.errors.push(error.to_string());
+                    self.errors.push(error);
                     self.synchronize();
                 }
             }
@@ -49,7 +77,11 @@ impl Parser {
             let value = self.assignment()?;
 
             return match expr {
-                Expr::Variable(token) => Ok(Expr::Assign(token, Box::new(value))),
+                Expr::Variable(token, id) => Ok(Expr::Assign(token, Box::new(value), id)),
+                Expr::Get(object, name) => Ok(Expr::Set(object, name, Box::new(value))),
+                Expr::Index(object, bracket, index) => {
+                    Ok(Expr::SetIndex(object, bracket, index, Box::new(value)))
+                }
                 _ => Err(ParseError {
                     token: equals,
                     message: "Invalid assignment target.".to_string(),
@@ -57,9 +89,53 @@ impl Parser {
             };
         }
 
+        if self.match_any(&[
+            TokenType::PlusEqual,
+            TokenType::MinusEqual,
+            TokenType::StarEqual,
+            TokenType::SlashEqual,
+            TokenType::PercentEqual,
+        ]) {
+            let operator = self.previous().clone();
+            let value = self.assignment()?;
+            return Self::compound(expr, operator, value, false);
+        }
+
         Ok(expr)
     }
 
+    /// Builds `target op= value`, and `++`/`--` with a `value` of 1. The
+    /// binary operator keeps the span of the compound one for error messages.
+    fn compound(
+        target: Expr,
+        operator: Token,
+        value: Expr,
+        postfix: bool,
+    ) -> Result<Expr, ParseError> {
+        if !matches!(target, Expr::Variable(..) | Expr::Get(..) | Expr::Index(..)) {
+            return Err(ParseError {
+                token: operator,
+                message: "Invalid assignment target.".to_string(),
+            });
+        }
+
+        let (token_type, lexeme) = match operator.token_type {
+            TokenType::PlusEqual | TokenType::PlusPlus => (TokenType::Plus, "+"),
+            TokenType::MinusEqual | TokenType::MinusMinus => (TokenType::Minus, "-"),
+            TokenType::StarEqual => (TokenType::Star, "*"),
+            TokenType::SlashEqual => (TokenType::Slash, "/"),
+            _ => (TokenType::Percent, "%"),
+        };
+        let binary = Token::new(token_type, lexeme.to_string(), Literal::Nil, operator.span);
+
+        Ok(Expr::Compound(
+            Box::new(target),
+            binary,
+            Box::new(value),
+            postfix,
+        ))
+    }
+
     fn or(&mut self) -> Result<Expr, ParseError> {
         let mut expr = self.and()?;
 
@@ -97,7 +173,7 @@ impl Parser {
     }
 
     fn comparison(&mut self) -> Result<Expr, ParseError> {
-        let mut expr = self.term()?;
+        let mut expr = self.bit_or()?;
 
         while self.match_any(&[
             TokenType::Greater,
@@ -105,6 +181,56 @@ impl Parser {
             TokenType::Less,
             TokenType::LessEqual,
         ]) {
+            let operator = self.previous().clone();
+            let right = self.bit_or()?;
+            expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
+        }
+
+        Ok(expr)
+    }
+
+    // Bitwise operators bind tighter than comparisons, so `n & 1 == 0` tests
+    // the lowest bit.
+    fn bit_or(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.bit_xor()?;
+
+        while self.match_any(&[TokenType::Pipe]) {
+            let operator = self.previous().clone();
+            let right = self.bit_xor()?;
+            expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
+        }
+
+        Ok(expr)
+    }
+
+    fn bit_xor(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.bit_and()?;
+
+        while self.match_any(&[TokenType::Caret]) {
+            let operator = self.previous().clone();
+            let right = self.bit_and()?;
+            expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
+        }
+
+        Ok(expr)
+    }
+
+    fn bit_and(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.shift()?;
+
+        while self.match_any(&[TokenType::Ampersand]) {
+            let operator = self.previous().clone();
+            let right = self.shift()?;
+            expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
+        }
+
+        Ok(expr)
+    }
+
+    fn shift(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.term()?;
+
+        while self.match_any(&[TokenType::LessLess, TokenType::GreaterGreater]) {
             let operator = self.previous().clone();
             let right = self.term()?;
             expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
@@ -128,7 +254,12 @@ impl Parser {
     fn factor(&mut self) -> Result<Expr, ParseError> {
         let mut expr = self.unary()?;
 
-        while self.match_any(&[TokenType::Slash, TokenType::Star]) {
+        while self.match_any(&[
+            TokenType::Slash,
+            TokenType::Star,
+            TokenType::Percent,
+            TokenType::TildeSlash,
+        ]) {
             let operator = self.previous().clone();
             let right = self.unary()?;
             expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
@@ -138,13 +269,33 @@ impl Parser {
     }
 
     fn unary(&mut self) -> Result<Expr, ParseError> {
-        if self.match_any(&[TokenType::Bang, TokenType::Minus]) {
+        if self.match_any(&[TokenType::Bang, TokenType::Minus, TokenType::Tilde]) {
             let operator = self.previous().clone();
             let right = self.unary()?;
             return Ok(Expr::Unary(operator, Box::new(right)));
         }
 
-        self.call()
+        if self.match_any(&[TokenType::PlusPlus, TokenType::MinusMinus]) {
+            let operator = self.previous().clone();
+            let target = self.unary()?;
+            return Self::compound(target, operator, Expr::Literal(Literal::Integer(1)), false);
+        }
+
+        self.power()
+    }
+
+    // `**` is right-associative and binds tighter than a unary operator on
+    // its left, so `-2 ** 2` is -4 while `2 ** -1` is 0.5.
+    fn power(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.call()?;
+
+        if self.match_any(&[TokenType::StarStar]) {
+            let operator = self.previous().clone();
+            let right = self.unary()?;
+            return Ok(Expr::Binary(Box::new(expr), operator, Box::new(right)));
+        }
+
+        Ok(expr)
     }
 
     fn call(&mut self) -> Result<Expr, ParseError> {
@@ -153,23 +304,56 @@ impl Parser {
         loop {
             if self.match_any(&[TokenType::LeftParen]) {
                 expr = self.finish_call(expr)?;
+            } else if self.match_any(&[TokenType::Dot]) {
+                let name =
+                    self.consume(TokenType::Identifier, "Expect property name after '.'.")?;
+                expr = Expr::Get(Box::new(expr), name.clone());
+            } else if self.match_any(&[TokenType::LeftBracket]) {
+                let bracket = self.previous().clone();
+                let index = self.expression()?;
+                self.consume(TokenType::RightBracket, "Expect ']' after index.")?;
+                expr = Expr::Index(Box::new(expr), bracket, Box::new(index));
             } else {
                 break;
             }
         }
 
+        if self.match_any(&[TokenType::PlusPlus, TokenType::MinusMinus]) {
+            let operator = self.previous().clone();
+            return Self::compound(expr, operator, Expr::Literal(Literal::Integer(1)), true);
+        }
+
         Ok(expr)
     }
 
     fn finish_call(&mut self, callee: Expr) -> Result<Expr, ParseError> {
         let mut arguments = Vec::new();
+        let mut named: Vec<(Token, Expr)> = Vec::new();
 
         if !self.check(TokenType::RightParen) {
             loop {
-                if arguments.len() >= 255 {
+                if arguments.len() + named.len() >= 255 {
                     return Err(self.error_at_current("Cannot have more than 255 arguments."));
                 }
-                arguments.push(self.expression()?);
+
+                if self.check(TokenType::Identifier) && self.check_ahead(1, TokenType::Colon) {
+                    let name = self.advance().clone();
+                    self.advance();
+                    named.push((name, self.expression()?));
+                } else {
+                    let start = self.peek().clone();
+                    let argument = self.expression()?;
+                    // Reported without unwinding, like `loop_jump`.
+                    if !named.is_empty() {
+                        self.errors.push(ParseError {
+                            token: start,
+                            message: "Positional arguments can't follow named arguments."
+                                .to_string(),
+                        });
+                    }
+                    arguments.push(argument);
+                }
+
                 if !self.match_any(&[TokenType::Comma]) {
                     break;
                 }
@@ -178,7 +362,12 @@ impl Parser {
 
         let paren = self.consume(TokenType::RightParen, "Expect ')' after arguments.")?;
 
-        Ok(Expr::Call(Box::new(callee), paren.clone(), arguments))
+        Ok(Expr::Call(
+            Box::new(callee),
+            paren.clone(),
+            arguments,
+            named,
+        ))
     }
 
     fn primary(&mut self) -> Result<Expr, ParseError> {
@@ -196,8 +385,33 @@ impl Parser {
             return Ok(Expr::Literal(self.previous().literal.clone()));
         }
 
+        if self.match_any(&[TokenType::Interpolation]) {
+            return self.interpolation();
+        }
+
+        if self.match_any(&[TokenType::Super]) {
+            let keyword = self.previous().clone();
+            self.consume(TokenType::Dot, "Expect '.' after 'super'.")?;
+            let method = self
+                .consume(TokenType::Identifier, "Expect superclass method name.")?
+                .clone();
+            return Ok(Expr::Super(keyword, method, Expr::next_id()));
+        }
+
+        if self.match_any(&[TokenType::This]) {
+            return Ok(Expr::This(self.previous().clone(), Expr::next_id()));
+        }
+
         if self.match_any(&[TokenType::Identifier]) {
-            return Ok(Expr::Variable(self.previous().clone()));
+            return Ok(Expr::Variable(self.previous().clone(), Expr::next_id()));
+        }
+
+        if self.match_any(&[TokenType::Fun]) {
+            return self.lambda();
+        }
+
+        if self.check(TokenType::LeftParen) && self.arrow_ahead() {
+            return self.arrow();
         }
 
         if self.match_any(&[TokenType::LeftParen]) {
@@ -206,9 +420,140 @@ impl Parser {
             return Ok(Expr::Grouping(Box::new(expr)));
         }
 
+        if self.match_any(&[TokenType::LeftBracket]) {
+            return self.list();
+        }
+
+        // A `{` that starts a statement has already been taken as a block.
+        if self.match_any(&[TokenType::LeftBrace]) {
+            return self.map();
+        }
+
         Err(self.error_at_current("Expect expression."))
     }
 
+    fn lambda(&mut self) -> Result<Expr, ParseError> {
+        let name = self.lambda_name();
+        self.consume(TokenType::LeftParen, "Expect '(' after 'fun'.")?;
+        let params = self.parameters()?;
+        self.consume(
+            TokenType::LeftBrace,
+            "Expect '{' before anonymous function body.",
+        )?;
+        let body = self.function_body()?;
+        Ok(Expr::Lambda(name, params, body.into()))
+    }
+
+    /// Parses `(params) => expr`, which returns `expr`.
+    fn arrow(&mut self) -> Result<Expr, ParseError> {
+        let name = self.lambda_name();
+        self.consume(TokenType::LeftParen, "Expect '(' before parameters.")?;
+        let params = self.parameters()?;
+        let arrow = self
+            .consume(TokenType::EqualGreater, "Expect '=>' after parameters.")?
+            .clone();
+
+        let enclosing_loops = std::mem::take(&mut self.loop_depth);
+        let value = self.assignment();
+        self.loop_depth = enclosing_loops;
+
+        Ok(Expr::Lambda(
+            name,
+            params,
+            Rc::from([Stmt::Return(arrow, Some(value?))]),
+        ))
+    }
+
+    fn lambda_name(&self) -> Token {
+        let span = self.peek().span;
+        let name = format!("anonymous@{}", span.line);
+        Token::new(TokenType::Identifier, name, Literal::Nil, span)
+    }
+
+    /// Whether the current `(` opens the parameter list of an arrow function
+    /// rather than a grouping, that is whether `=>` follows its matching `)`.
+    fn arrow_ahead(&self) -> bool {
+        let mut depth = 0;
+        for (distance, token) in self.tokens[self.current..].iter().enumerate() {
+            match token.token_type {
+                TokenType::LeftParen => depth += 1,
+                TokenType::RightParen => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return self.check_ahead(distance + 1, TokenType::EqualGreater);
+                    }
+                }
+                TokenType::Eof => return false,
+                _ => {}
+            }
+        }
+        false
+    }
+
+    /// Parses the elements of a list literal, allowing a trailing comma.
+    fn list(&mut self) -> Result<Expr, ParseError> {
+        let bracket = self.previous().clone();
+        let mut elements = Vec::new();
+
+        while !self.check(TokenType::RightBracket) {
+            elements.push(self.expression()?);
+            if !self.match_any(&[TokenType::Comma]) {
+                break;
+            }
+        }
+
+        self.consume(TokenType::RightBracket, "Expect ']' after list elements.")?;
+        Ok(Expr::List(bracket, elements))
+    }
+
+    /// Parses the entries of a map literal, allowing a trailing comma.
+    fn map(&mut self) -> Result<Expr, ParseError> {
+        let brace = self.previous().clone();
+        let mut entries = Vec::new();
+
+        while !self.check(TokenType::RightBrace) {
+            let key = self.expression()?;
+            self.consume(TokenType::Colon, "Expect ':' after map key.")?;
+            let value = self.expression()?;
+            entries.push((key, value));
+
+            if !self.match_any(&[TokenType::Comma]) {
+                break;
+            }
+        }
+
+        self.consume(TokenType::RightBrace, "Expect '}' after map entries.")?;
+        Ok(Expr::Map(brace, entries))
+    }
+
+    /// Parses `"a${b}c"` into its parts `"a"`, `b` and `"c"`, leaving out
+    /// empty strings.
+    fn interpolation(&mut self) -> Result<Expr, ParseError> {
+        let token = self.previous().clone();
+        let mut parts = Vec::new();
+        if token.literal != Literal::String(String::new()) {
+            parts.push(Expr::Literal(token.literal.clone()));
+        }
+
+        loop {
+            parts.push(self.expression()?);
+
+            let done = !self.match_any(&[TokenType::Interpolation]);
+            if done {
+                self.consume(TokenType::String, "Expect end of string interpolation.")?;
+            }
+
+            let part = self.previous().literal.clone();
+            if part != Literal::String(String::new()) {
+                parts.push(Expr::Literal(part));
+            }
+
+            if done {
+                return Ok(Expr::Interpolation(token, parts));
+            }
+        }
+    }
+
     // Helper methods
     fn is_at_end(&self) -> bool {
         self.peek().token_type == TokenType::Eof
@@ -236,6 +581,32 @@ impl Parser {
         self.peek().token_type == token_type
     }
 
+    /// Checks the token `distance` places after the current one.
+    fn check_ahead(&self, distance: usize, token_type: TokenType) -> bool {
+        self.tokens
+            .get(self.current + distance)
+            .is_some_and(|token| token.token_type == token_type)
+    }
+
+    /// Whether the tokens after a `{` read as `key:`, the start of a map
+    /// literal rather than of a block.
+    fn starts_map_entry(&self) -> bool {
+        let mut depth = 0usize;
+        for token in &self.tokens[self.current..] {
+            match token.token_type {
+                TokenType::LeftParen | TokenType::LeftBracket => depth += 1,
+                TokenType::RightParen | TokenType::RightBracket => depth = depth.saturating_sub(1),
+                TokenType::Colon => return depth == 0,
+                TokenType::Semicolon
+                | TokenType::LeftBrace
+                | TokenType::RightBrace
+                | TokenType::Eof => return false,
+                _ => {}
+            }
+        }
+        false
+    }
+
     fn match_any(&mut self, types: &[TokenType]) -> bool {
         for &token_type in types {
             if self.check(token_type) {
@@ -277,7 +648,9 @@ impl Parser {
                 | TokenType::If
                 | TokenType::While
                 | TokenType::Print
-                | TokenType::Return => return,
+                | TokenType::Return
+                | TokenType::Break
+                | TokenType::Continue => return,
                 _ => {
                     self.advance();
                 }
@@ -286,12 +659,20 @@ impl Parser {
     }
 
     fn declaration(&mut self) -> Result<Stmt, ParseError> {
+        let doc = self.docs.remove(&self.current);
+
+        if self.match_any(&[TokenType::Class]) {
+            return self.class_declaration(doc);
+        }
+
         if self.match_any(&[TokenType::Var]) {
-            return self.var_declaration();
+            return self.var_declaration(doc);
         }
 
-        if self.match_any(&[TokenType::Fun]) {
-            return self.func_declaration("function");
+        // `fun (` starts an anonymous function expression instead.
+        if self.check(TokenType::Fun) && !self.check_ahead(1, TokenType::LeftParen) {
+            self.advance();
+            return self.func_declaration("function", doc);
         }
 
         self.statement()
@@ -310,6 +691,10 @@ impl Parser {
             return self.return_statement();
         }
 
+        if self.match_any(&[TokenType::Break, TokenType::Continue]) {
+            return self.loop_jump();
+        }
+
         if self.match_any(&[TokenType::While]) {
             return self.while_statement();
         }
@@ -319,6 +704,13 @@ impl Parser {
         }
 
         if self.match_any(&[TokenType::LeftBrace]) {
+            if self.starts_map_entry() {
+                return Err(ParseError {
+                    token: self.previous().clone(),
+                    message: "Wrap a map literal in parentheses to use it as a statement."
+                        .to_string(),
+                });
+            }
             return self.block_statement();
         }
 
@@ -358,22 +750,55 @@ impl Parser {
         Ok(Stmt::Return(keyword, value))
     }
 
+    fn loop_jump(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+        // Reported without unwinding: the statement parses fine, so there is
+        // nothing to recover from.
+        if self.loop_depth == 0 {
+            self.errors.push(ParseError {
+                message: format!("Can't use '{}' outside of a loop.", keyword.lexeme),
+                token: keyword.clone(),
+            });
+        }
+
+        self.consume(
+            TokenType::Semicolon,
+            &format!("Expect ';' after '{}'.", keyword.lexeme),
+        )?;
+
+        Ok(match keyword.token_type {
+            TokenType::Break => Stmt::Break(keyword),
+            _ => Stmt::Continue(keyword),
+        })
+    }
+
+    fn loop_body(&mut self) -> Result<Stmt, ParseError> {
+        self.loop_depth += 1;
+        let body = self.statement();
+        self.loop_depth -= 1;
+        body
+    }
+
     fn while_statement(&mut self) -> Result<Stmt, ParseError> {
         self.consume(TokenType::LeftParen, "Expect '(' after 'while'.")?;
         let condition = self.expression()?;
         self.consume(TokenType::RightParen, "Expect ')' after condition.")?;
 
-        let body = Box::new(self.statement()?);
-        Ok(Stmt::While(condition, body))
+        let body = Box::new(self.loop_body()?);
+        Ok(Stmt::While(condition, body, None))
     }
 
     fn for_statement(&mut self) -> Result<Stmt, ParseError> {
         self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?;
 
+        if self.check(TokenType::Var) && self.check_ahead(2, TokenType::In) {
+            return self.for_in_statement();
+        }
+
         let initializer = if self.match_any(&[TokenType::Semicolon]) {
             None
         } else if self.match_any(&[TokenType::Var]) {
-            Some(self.var_declaration()?)
+            Some(self.var_declaration(None)?)
         } else {
             Some(self.expression_statement()?)
         };
@@ -392,14 +817,10 @@ impl Parser {
         };
         self.consume(TokenType::RightParen, "Expect ')' after for clauses.")?;
 
-        let mut body = self.statement()?;
-
-        if let Some(inc) = increment {
-            body = Stmt::Block(vec![body, Stmt::Expr(inc)]);
-        }
+        let body = self.loop_body()?;
 
         let while_condition = condition.unwrap_or(Expr::Literal(Literal::Boolean(true)));
-        let while_loop = Stmt::While(while_condition, Box::new(body));
+        let while_loop = Stmt::While(while_condition, Box::new(body), increment);
 
         if let Some(init) = initializer {
             return Ok(Stmt::Block(vec![init, while_loop]));
@@ -408,6 +829,19 @@ impl Parser {
         Ok(while_loop)
     }
 
+    fn for_in_statement(&mut self) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::Var, "Expect 'var' in for-in loop.")?;
+        let name = self
+            .consume(TokenType::Identifier, "Expect variable name.")?
+            .clone();
+        self.consume(TokenType::In, "Expect 'in' after loop variable.")?;
+        let iterable = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after for-in clause.")?;
+
+        let body = self.loop_body()?;
+        Ok(Stmt::ForIn(name, iterable, Box::new(body)))
+    }
+
     fn block_statement(&mut self) -> Result<Stmt, ParseError> {
         let mut statements = Vec::new();
 
@@ -425,22 +859,92 @@ impl Parser {
         Ok(Stmt::Expr(expr))
     }
 
-    fn func_declaration(&mut self, kind: &str) -> Result<Stmt, ParseError> {
+    fn class_declaration(&mut self, doc: Option<String>) -> Result<Stmt, ParseError> {
+        let name = self
+            .consume(TokenType::Identifier, "Expect class name.")?
+            .clone();
+
+        let superclass = if self.match_any(&[TokenType::Less]) {
+            let superclass = self.consume(TokenType::Identifier, "Expect superclass name.")?;
+            Some(Expr::Variable(superclass.clone(), Expr::next_id()))
+        } else {
+            None
+        };
+
+        self.consume(TokenType::LeftBrace, "Expect '{' before class body.")?;
+
+        let mut methods = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            let doc = self.docs.remove(&self.current);
+            methods.push(self.func_declaration("method", doc)?);
+        }
+
+        self.consume(TokenType::RightBrace, "Expect '}' after class body.")?;
+        Ok(Stmt::Class(name, superclass, methods, doc))
+    }
+
+    fn func_declaration(&mut self, kind: &str, doc: Option<String>) -> Result<Stmt, ParseError> {
         self.consume(TokenType::Identifier, "Expect function name.")?;
         let name = self.previous().clone();
         self.consume(
             TokenType::LeftParen,
             &format!("Expect '(' after {} name.", kind),
         )?;
+        let params = self.parameters()?;
+        self.consume(
+            TokenType::LeftBrace,
+            &format!("Expect '{{' before {} body.", kind),
+        )?;
+        let body = self.function_body()?;
 
-        let mut params: Vec<Token> = Vec::new();
+        Ok(Stmt::Function(name, params, body.into(), doc))
+    }
+
+    /// Parses a parameter list after its opening parenthesis, up to and
+    /// including the closing one. Parameters with a default must follow the
+    /// required ones, and a `...rest` parameter must come last.
+    fn parameters(&mut self) -> Result<Vec<Param>, ParseError> {
+        let mut params: Vec<Param> = Vec::new();
         if !self.check(TokenType::RightParen) {
             loop {
                 if params.len() >= 255 {
                     return Err(self.error_at_current("Cannot have more than 255 parameters."));
                 }
-                self.consume(TokenType::Identifier, "Expect parameter name.")?;
-                params.push(self.previous().clone());
+
+                if self.match_any(&[TokenType::DotDotDot]) {
+                    let name = self
+                        .consume(TokenType::Identifier, "Expect parameter name after '...'.")?
+                        .clone();
+                    params.push(Param {
+                        name,
+                        default: None,
+                        rest: true,
+                    });
+                    self.consume(TokenType::RightParen, "Expect ')' after rest parameter.")?;
+                    return Ok(params);
+                }
+
+                let name = self
+                    .consume(TokenType::Identifier, "Expect parameter name.")?
+                    .clone();
+                let default = if self.match_any(&[TokenType::Equal]) {
+                    Some(self.expression()?)
+                } else {
+                    if params.last().is_some_and(|param| param.default.is_some()) {
+                        self.errors.push(ParseError {
+                            token: name.clone(),
+                            message:
+                                "A parameter without a default can't follow one with a default."
+                                    .to_string(),
+                        });
+                    }
+                    None
+                };
+                params.push(Param {
+                    name,
+                    default,
+                    rest: false,
+                });
 
                 if !self.match_any(&[TokenType::Comma]) {
                     break;
@@ -449,19 +953,23 @@ impl Parser {
         }
 
         self.consume(TokenType::RightParen, "Expect ')' after parameters.")?;
-        self.consume(
-            TokenType::LeftBrace,
-            &format!("Expect '{{' before {} body.", kind),
-        )?;
+        Ok(params)
+    }
+
+    /// Parses a function body after its opening brace.
+    fn function_body(&mut self) -> Result<Vec<Stmt>, ParseError> {
+        // A function body starts outside of any loop.
+        let enclosing_loops = std::mem::take(&mut self.loop_depth);
+        let body = self.block_statement();
+        self.loop_depth = enclosing_loops;
 
-        let body = self.block_statement()?;
-        match body {
-            Stmt::Block(statements) => Ok(Stmt::Function(name.clone(), params, statements)),
+        match body? {
+            Stmt::Block(statements) => Ok(statements),
             _ => Err(self.error_at_current("Expected block statement for function body.")),
         }
     }
 
-    fn var_declaration(&mut self) -> Result<Stmt, ParseError> {
+    fn var_declaration(&mut self, doc: Option<String>) -> Result<Stmt, ParseError> {
         self.consume(TokenType::Identifier, "Expect variable name.")?;
         let name = self.previous().clone();
         let mut initializer = None;
@@ -475,6 +983,6 @@ impl Parser {
             "Expect ';' after variable declaration.",
         )?;
 
-        Ok(Stmt::Var(name.lexeme.clone(), initializer))
+        Ok(Stmt::Var(name, initializer, doc))
     }
 }
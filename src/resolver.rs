@@ -0,0 +1,441 @@
+use crate::errors::{ParseError, RuntimeError};
+use crate::interpreter::Interpreter;
+use crate::models::exr::{Expr, ExprVisitor};
+use crate::models::literals::Literal;
+use crate::models::params::Param;
+use crate::models::stmt::{Stmt, StmtVisitor};
+use crate::models::tokens::Token;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Method,
+    Initializer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+pub struct Resolver<'a> {
+    // Where resolved variables are recorded; `None` when only the static
+    // errors are wanted.
+    interpreter: Option<&'a mut Interpreter>,
+    scopes: Vec<HashMap<String, bool>>,
+    current_function: FunctionType,
+    current_class: ClassType,
+    pub errors: Vec<ParseError>,
+}
+
+impl ExprVisitor<()> for Resolver<'_> {
+    fn visit_literal_expr(&mut self, _literal: &Literal) -> Result<(), RuntimeError> {
+        Ok(())
+    }
+
+    fn visit_logical_expr(
+        &mut self,
+        left: &Expr,
+        _operator: &Token,
+        right: &Expr,
+    ) -> Result<(), RuntimeError> {
+        self.resolve_expr(left);
+        self.resolve_expr(right);
+        Ok(())
+    }
+
+    fn visit_binary_expr(
+        &mut self,
+        left: &Expr,
+        _operator: &Token,
+        right: &Expr,
+    ) -> Result<(), RuntimeError> {
+        self.resolve_expr(left);
+        self.resolve_expr(right);
+        Ok(())
+    }
+
+    fn visit_call_expr(
+        &mut self,
+        callee: &Expr,
+        _paren: &Token,
+        arguments: &[Expr],
+        named: &[(Token, Expr)],
+    ) -> Result<(), RuntimeError> {
+        self.resolve_expr(callee);
+        for argument in arguments {
+            self.resolve_expr(argument);
+        }
+        for (_, argument) in named {
+            self.resolve_expr(argument);
+        }
+        Ok(())
+    }
+
+    fn visit_grouping_expr(&mut self, expression: &Expr) -> Result<(), RuntimeError> {
+        self.resolve_expr(expression);
+        Ok(())
+    }
+
+    fn visit_unary_expr(&mut self, _operator: &Token, right: &Expr) -> Result<(), RuntimeError> {
+        self.resolve_expr(right);
+        Ok(())
+    }
+
+    fn visit_variable_expr(&mut self, token: &Token, id: usize) -> Result<(), RuntimeError> {
+        if let Some(scope) = self.scopes.last() {
+            if scope.get(&token.lexeme) == Some(&false) {
+                self.error(token, "Can't read local variable in its own initializer.");
+            }
+        }
+
+        self.resolve_local(token, id);
+        Ok(())
+    }
+
+    fn visit_assign_expr(
+        &mut self,
+        token: &Token,
+        expr: &Expr,
+        id: usize,
+    ) -> Result<(), RuntimeError> {
+        self.resolve_expr(expr);
+        self.resolve_local(token, id);
+        Ok(())
+    }
+
+    fn visit_get_expr(&mut self, object: &Expr, _name: &Token) -> Result<(), RuntimeError> {
+        self.resolve_expr(object);
+        Ok(())
+    }
+
+    fn visit_set_expr(
+        &mut self,
+        object: &Expr,
+        _name: &Token,
+        value: &Expr,
+    ) -> Result<(), RuntimeError> {
+        self.resolve_expr(value);
+        self.resolve_expr(object);
+        Ok(())
+    }
+
+    fn visit_list_expr(&mut self, _bracket: &Token, elements: &[Expr]) -> Result<(), RuntimeError> {
+        for element in elements {
+            self.resolve_expr(element);
+        }
+        Ok(())
+    }
+
+    fn visit_interpolation_expr(
+        &mut self,
+        _token: &Token,
+        parts: &[Expr],
+    ) -> Result<(), RuntimeError> {
+        for part in parts {
+            self.resolve_expr(part);
+        }
+        Ok(())
+    }
+
+    fn visit_map_expr(
+        &mut self,
+        _brace: &Token,
+        entries: &[(Expr, Expr)],
+    ) -> Result<(), RuntimeError> {
+        for (key, value) in entries {
+            self.resolve_expr(key);
+            self.resolve_expr(value);
+        }
+        Ok(())
+    }
+
+    fn visit_index_expr(
+        &mut self,
+        object: &Expr,
+        _bracket: &Token,
+        index: &Expr,
+    ) -> Result<(), RuntimeError> {
+        self.resolve_expr(object);
+        self.resolve_expr(index);
+        Ok(())
+    }
+
+    fn visit_set_index_expr(
+        &mut self,
+        object: &Expr,
+        _bracket: &Token,
+        index: &Expr,
+        value: &Expr,
+    ) -> Result<(), RuntimeError> {
+        self.resolve_expr(object);
+        self.resolve_expr(index);
+        self.resolve_expr(value);
+        Ok(())
+    }
+
+    fn visit_compound_expr(
+        &mut self,
+        target: &Expr,
+        _operator: &Token,
+        value: &Expr,
+        _postfix: bool,
+    ) -> Result<(), RuntimeError> {
+        self.resolve_expr(target);
+        self.resolve_expr(value);
+        Ok(())
+    }
+
+    fn visit_lambda_expr(
+        &mut self,
+        _name: &Token,
+        params: &[Param],
+        body: &Rc<[Stmt]>,
+    ) -> Result<(), RuntimeError> {
+        self.resolve_function(params, body, FunctionType::Function);
+        Ok(())
+    }
+
+    fn visit_this_expr(&mut self, keyword: &Token, id: usize) -> Result<(), RuntimeError> {
+        if self.current_class == ClassType::None {
+            self.error(keyword, "Can't use 'this' outside of a class.");
+            return Ok(());
+        }
+
+        self.resolve_local(keyword, id);
+        Ok(())
+    }
+
+    fn visit_super_expr(
+        &mut self,
+        keyword: &Token,
+        _method: &Token,
+        id: usize,
+    ) -> Result<(), RuntimeError> {
+        match self.current_class {
+            ClassType::None => self.error(keyword, "Can't use 'super' outside of a class."),
+            ClassType::Class => {
+                self.error(keyword, "Can't use 'super' in a class with no superclass.")
+            }
+            ClassType::Subclass => self.resolve_local(keyword, id),
+        }
+        Ok(())
+    }
+}
+
+impl StmtVisitor<()> for Resolver<'_> {
+    fn visit_expr_stmt(&mut self, expr: &Expr) {
+        self.resolve_expr(expr);
+    }
+
+    fn visit_class_stmt(&mut self, name: &Token, superclass: &Option<Expr>, methods: &[Stmt]) {
+        let enclosing_class = self.current_class;
+        self.current_class = ClassType::Class;
+
+        self.declare(name);
+        self.define(name);
+
+        if let Some(superclass) = superclass {
+            if let Expr::Variable(superclass_name, _) = superclass {
+                if superclass_name.lexeme == name.lexeme {
+                    self.error(superclass_name, "A class can't inherit from itself.");
+                }
+            }
+
+            self.current_class = ClassType::Subclass;
+            self.resolve_expr(superclass);
+
+            self.begin_scope();
+            if let Some(scope) = self.scopes.last_mut() {
+                scope.insert("super".to_string(), true);
+            }
+        }
+
+        self.begin_scope();
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert("this".to_string(), true);
+        }
+
+        for method in methods {
+            if let Stmt::Function(method_name, params, body, _) = method {
+                let kind = if method_name.lexeme == "init" {
+                    FunctionType::Initializer
+                } else {
+                    FunctionType::Method
+                };
+                self.resolve_function(params, body, kind);
+            }
+        }
+
+        self.end_scope();
+
+        if superclass.is_some() {
+            self.end_scope();
+        }
+
+        self.current_class = enclosing_class;
+    }
+
+    fn visit_function_stmt(&mut self, name: &Token, params: &[Param], body: &Rc<[Stmt]>) {
+        self.declare(name);
+        self.define(name);
+        self.resolve_function(params, body, FunctionType::Function);
+    }
+
+    fn visit_if_stmt(&mut self, cond: &Expr, then_branch: &Stmt, else_branch: &Option<Box<Stmt>>) {
+        self.resolve_expr(cond);
+        self.resolve_stmt(then_branch);
+        if let Some(else_branch) = else_branch {
+            self.resolve_stmt(else_branch);
+        }
+    }
+
+    fn visit_print_stmt(&mut self, expr: &Expr) {
+        self.resolve_expr(expr);
+    }
+
+    fn visit_while_stmt(&mut self, cond: &Expr, body: &Stmt, increment: &Option<Expr>) {
+        self.resolve_expr(cond);
+        self.resolve_stmt(body);
+        if let Some(increment) = increment {
+            self.resolve_expr(increment);
+        }
+    }
+
+    fn visit_for_in_stmt(&mut self, name: &Token, iterable: &Expr, body: &Stmt) {
+        self.resolve_expr(iterable);
+
+        self.begin_scope();
+        self.declare(name);
+        self.define(name);
+        self.resolve_stmt(body);
+        self.end_scope();
+    }
+
+    fn visit_break_stmt(&mut self, _keyword: &Token) {}
+
+    fn visit_continue_stmt(&mut self, _keyword: &Token) {}
+
+    fn visit_return_stmt(&mut self, token: &Token, expr: &Option<Expr>) {
+        if self.current_function == FunctionType::None {
+            self.error(token, "Can't return from top-level code.");
+        }
+
+        if let Some(expr) = expr {
+            if self.current_function == FunctionType::Initializer {
+                self.error(token, "Can't return a value from an initializer.");
+            }
+            self.resolve_expr(expr);
+        }
+    }
+
+    fn visit_var_stmt(&mut self, name: &Token, expr: &Option<Expr>) {
+        self.declare(name);
+        if let Some(expr) = expr {
+            self.resolve_expr(expr);
+        }
+        self.define(name);
+    }
+
+    fn visit_block_stmt(&mut self, stmts: &[Stmt]) {
+        self.begin_scope();
+        self.resolve(stmts);
+        self.end_scope();
+    }
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(interpreter: Option<&'a mut Interpreter>) -> Self {
+        Self {
+            interpreter,
+            scopes: Vec::new(),
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn resolve(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.resolve_stmt(stmt);
+        }
+    }
+
+    fn resolve_stmt(&mut self, stmt: &Stmt) {
+        stmt.accept(self);
+    }
+
+    fn resolve_expr(&mut self, expr: &Expr) {
+        // The resolver reports its errors through `self.errors`, never through the result.
+        let _ = expr.accept(self);
+    }
+
+    fn resolve_function(&mut self, params: &[Param], body: &[Stmt], kind: FunctionType) {
+        let enclosing_function = self.current_function;
+        self.current_function = kind;
+
+        self.begin_scope();
+        for param in params {
+            // A default sees the parameters before it, not its own.
+            if let Some(default) = &param.default {
+                self.resolve_expr(default);
+            }
+            self.declare(&param.name);
+            self.define(&param.name);
+        }
+        self.resolve(body);
+        self.end_scope();
+
+        self.current_function = enclosing_function;
+    }
+
+    fn resolve_local(&mut self, token: &Token, id: usize) {
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            if scope.contains_key(&token.lexeme) {
+                if let Some(interpreter) = &mut self.interpreter {
+                    interpreter.resolve(id, depth);
+                }
+                return;
+            }
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Token) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+
+        if scope.contains_key(&name.lexeme) {
+            self.error(name, "Already a variable with this name in this scope.");
+            return;
+        }
+
+        scope.insert(name.lexeme.clone(), false);
+    }
+
+    fn define(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.lexeme.clone(), true);
+        }
+    }
+
+    fn error(&mut self, token: &Token, message: &str) {
+        self.errors.push(ParseError {
+            token: token.clone(),
+            message: message.to_string(),
+        });
+    }
+}
@@ -1,23 +1,28 @@
+use crate::errors::ScanError;
 use crate::models::literals::Literal;
 use crate::models::token_type::{TokenType, KEYWORDS};
-use crate::models::tokens::Token;
+use crate::models::tokens::{Span, Token};
+use unicode_ident::{is_xid_continue, is_xid_start};
 
+/// Turns source text into tokens. `start` and `current` are byte offsets into
+/// `source` and always sit on character boundaries.
 #[derive(Debug, Clone)]
 pub struct Scanner {
     source: String,
     pub tokens: Vec<Token>,
-    pub errors: Vec<String>,
+    pub errors: Vec<ScanError>,
     start: usize,
     current: usize,
     line: usize,
-    chars: Vec<char>,
-    length: usize,
+    line_start: usize,
+    start_line: usize,
+    start_column: usize,
+    // One entry per `${` being scanned, counting the braces opened inside it.
+    interpolations: Vec<usize>,
 }
 
 impl Scanner {
     pub fn new(source: String) -> Self {
-        let chars = source.chars().collect();
-        let length = source.len();
         Self {
             source,
             tokens: Vec::new(),
@@ -25,27 +30,61 @@ impl Scanner {
             start: 0,
             current: 0,
             line: 1,
-            chars,
-            length,
+            line_start: 0,
+            start_line: 1,
+            start_column: 1,
+            interpolations: Vec::new(),
         }
     }
 
     pub fn scan_tokens(&mut self) {
         while !self.is_at_end() {
             self.start = self.current;
+            self.start_line = self.line;
+            self.start_column = self.column();
             self.scan_token();
         }
 
+        self.start = self.current;
+        self.start_line = self.line;
+        self.start_column = self.column();
+        if !self.interpolations.is_empty() {
+            self.error("Unterminated string interpolation.".to_string());
+        }
+
+        self.start = self.current;
+        self.start_line = self.line;
+        self.start_column = self.column();
         self.tokens.push(Token::new(
             TokenType::Eof,
             String::new(),
             Literal::Nil,
-            self.line,
+            self.span(),
         ));
     }
 
+    /// The 1-based column of `current`, counted in characters.
+    fn column(&self) -> usize {
+        self.source[self.line_start..self.current].chars().count() + 1
+    }
+
+    /// The span from the start of the current token up to `current`.
+    fn span(&self) -> Span {
+        Span::new(
+            self.start_line,
+            self.start_column,
+            self.start,
+            self.current - self.start,
+        )
+    }
+
+    fn newline(&mut self) {
+        self.line += 1;
+        self.line_start = self.current;
+    }
+
     fn is_at_end(&self) -> bool {
-        self.current >= self.length
+        self.current >= self.source.len()
     }
 
     fn scan_token(&mut self) {
@@ -54,14 +93,86 @@ impl Scanner {
         match c {
             '(' => self.add_token_type(TokenType::LeftParen),
             ')' => self.add_token_type(TokenType::RightParen),
-            '{' => self.add_token_type(TokenType::LeftBrace),
-            '}' => self.add_token_type(TokenType::RightBrace),
+            '[' => self.add_token_type(TokenType::LeftBracket),
+            ']' => self.add_token_type(TokenType::RightBracket),
+            ':' => self.add_token_type(TokenType::Colon),
+            '{' => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth += 1;
+                }
+                self.add_token_type(TokenType::LeftBrace);
+            }
+            '}' => match self.interpolations.last_mut() {
+                Some(0) => {
+                    self.interpolations.pop();
+                    self.end_interpolation();
+                }
+                Some(depth) => {
+                    *depth -= 1;
+                    self.add_token_type(TokenType::RightBrace);
+                }
+                None => self.add_token_type(TokenType::RightBrace),
+            },
             ',' => self.add_token_type(TokenType::Comma),
-            '.' => self.add_token_type(TokenType::Dot),
-            '-' => self.add_token_type(TokenType::Minus),
-            '+' => self.add_token_type(TokenType::Plus),
+            '.' => {
+                let token_type = if self.peek() == '.' && self.peek_next() == '.' {
+                    self.current += 2;
+                    TokenType::DotDotDot
+                } else {
+                    TokenType::Dot
+                };
+                self.add_token_type(token_type);
+            }
+            '-' => {
+                let token_type = if self.matches('-') {
+                    TokenType::MinusMinus
+                } else if self.matches('=') {
+                    TokenType::MinusEqual
+                } else {
+                    TokenType::Minus
+                };
+                self.add_token_type(token_type);
+            }
+            '+' => {
+                let token_type = if self.matches('+') {
+                    TokenType::PlusPlus
+                } else if self.matches('=') {
+                    TokenType::PlusEqual
+                } else {
+                    TokenType::Plus
+                };
+                self.add_token_type(token_type);
+            }
             ';' => self.add_token_type(TokenType::Semicolon),
-            '*' => self.add_token_type(TokenType::Star),
+            '*' => {
+                let token_type = if self.matches('*') {
+                    TokenType::StarStar
+                } else if self.matches('=') {
+                    TokenType::StarEqual
+                } else {
+                    TokenType::Star
+                };
+                self.add_token_type(token_type);
+            }
+            '%' => {
+                let token_type = if self.matches('=') {
+                    TokenType::PercentEqual
+                } else {
+                    TokenType::Percent
+                };
+                self.add_token_type(token_type);
+            }
+            '&' => self.add_token_type(TokenType::Ampersand),
+            '|' => self.add_token_type(TokenType::Pipe),
+            '^' => self.add_token_type(TokenType::Caret),
+            '~' => {
+                let token_type = if self.matches('/') {
+                    TokenType::TildeSlash
+                } else {
+                    TokenType::Tilde
+                };
+                self.add_token_type(token_type);
+            }
             '!' => {
                 let token_type = if self.matches('=') {
                     TokenType::BangEqual
@@ -73,6 +184,8 @@ impl Scanner {
             '=' => {
                 let token_type = if self.matches('=') {
                     TokenType::EqualEqual
+                } else if self.matches('>') {
+                    TokenType::EqualGreater
                 } else {
                     TokenType::Equal
                 };
@@ -81,6 +194,8 @@ impl Scanner {
             '<' => {
                 let token_type = if self.matches('=') {
                     TokenType::LessEqual
+                } else if self.matches('<') {
+                    TokenType::LessLess
                 } else {
                     TokenType::Less
                 };
@@ -89,6 +204,8 @@ impl Scanner {
             '>' => {
                 let token_type = if self.matches('=') {
                     TokenType::GreaterEqual
+                } else if self.matches('>') {
+                    TokenType::GreaterGreater
                 } else {
                     TokenType::Greater
                 };
@@ -96,28 +213,35 @@ impl Scanner {
             }
             '/' => {
                 if self.matches('/') {
-                    while self.peek() != '\n' && !self.is_at_end() {
-                        self.advance();
-                    }
+                    self.line_comment();
+                } else if self.matches('*') {
+                    self.block_comment();
+                } else if self.matches('=') {
+                    self.add_token_type(TokenType::SlashEqual);
                 } else {
                     self.add_token_type(TokenType::Slash);
                 }
             }
             ' ' | '\r' | '\t' => (),
-            '\n' => self.line += 1,
+            '\n' => self.newline(),
             '"' => self.string(),
-            c if c.is_ascii_digit() => self.number(),
-            c if c.is_ascii_alphabetic() || c == '_' => self.identifier(),
+            c if c.is_ascii_digit() => self.number(c),
+            c if c == '_' || is_xid_start(c) => self.identifier(),
 
-            _ => self
-                .errors
-                .push(format!("line {}: Unexpected character: {}", self.line, c)),
+            _ => self.error(format!("Unexpected character: {}", c)),
         }
     }
 
+    fn error(&mut self, message: String) {
+        self.errors.push(ScanError {
+            span: self.span(),
+            message,
+        });
+    }
+
     fn advance(&mut self) -> char {
-        let c = self.chars[self.current];
-        self.current += 1;
+        let c = self.peek();
+        self.current += c.len_utf8();
         c
     }
 
@@ -126,24 +250,51 @@ impl Scanner {
             return false;
         }
 
-        self.current += 1;
+        self.current += expected.len_utf8();
         true
     }
 
     fn peek(&self) -> char {
-        if self.is_at_end() {
-            return '\0';
-        }
-
-        self.chars[self.current]
+        self.source[self.current..].chars().next().unwrap_or('\0')
     }
 
     fn peek_next(&self) -> char {
-        if self.current + 1 >= self.length {
-            return '\0';
+        self.source[self.current..].chars().nth(1).unwrap_or('\0')
+    }
+
+    /// Skips a `//` comment. Exactly three slashes make it a doc comment,
+    /// whose text is kept for the declaration that follows.
+    fn line_comment(&mut self) {
+        let is_doc = self.peek() == '/' && self.peek_next() != '/';
+
+        while self.peek() != '\n' && !self.is_at_end() {
+            self.advance();
+        }
+
+        if is_doc {
+            let text = &self.source[self.start + 3..self.current];
+            let text = text.strip_prefix(' ').unwrap_or(text).trim_end();
+            self.add_token(TokenType::DocComment, Literal::String(text.to_string()));
         }
+    }
+
+    /// Skips a `/* ... */` comment, which may contain nested block comments.
+    fn block_comment(&mut self) {
+        let mut depth = 1;
+
+        while depth > 0 {
+            if self.is_at_end() {
+                self.error("Unterminated block comment.".to_string());
+                return;
+            }
 
-        self.chars[self.current + 1]
+            match self.advance() {
+                '/' if self.matches('*') => depth += 1,
+                '*' if self.matches('/') => depth -= 1,
+                '\n' => self.newline(),
+                _ => (),
+            }
+        }
     }
 
     fn add_token_type(&mut self, token_type: TokenType) {
@@ -152,58 +303,188 @@ impl Scanner {
 
     fn add_token(&mut self, token_type: TokenType, literal: Literal) {
         let text = &self.source[self.start..self.current];
-        self.tokens
-            .push(Token::new(token_type, text.to_string(), literal, self.line));
+        self.tokens.push(Token::new(
+            token_type,
+            text.to_string(),
+            literal,
+            self.span(),
+        ));
     }
 
+    /// Scans the rest of a string literal, processing escapes. When it runs
+    /// into `${` it emits the text so far as an `Interpolation` token and
+    /// returns; scanning resumes here once the matching `}` is reached.
     fn string(&mut self) {
-        while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
+        let mut value = String::new();
+
+        loop {
+            if self.is_at_end() {
+                self.error("Unterminated string.".to_string());
+                return;
+            }
+
+            match self.advance() {
+                '"' => break,
+                '\\' => {
+                    if let Some(c) = self.escape() {
+                        value.push(c);
+                    }
+                }
+                '$' if self.matches('{') => {
+                    self.interpolations.push(0);
+                    self.add_token(TokenType::Interpolation, Literal::String(value));
+                    return;
+                }
+                c => {
+                    if c == '\n' {
+                        self.newline();
+                    }
+                    value.push(c);
+                }
             }
-            self.advance();
         }
 
-        if self.is_at_end() {
-            self.errors
-                .push(format!("line {}: Unterminated string.", self.line));
-            return;
+        self.add_token(TokenType::String, Literal::String(value));
+    }
+
+    fn end_interpolation(&mut self) {
+        if self.tokens.last().map(|token| token.token_type) == Some(TokenType::Interpolation) {
+            self.error("Expect expression in string interpolation.".to_string());
         }
 
-        self.advance();
+        self.string();
+    }
 
-        let value = &self.source[self.start + 1..self.current - 1];
-        self.add_token(TokenType::String, Literal::String(value.to_string()));
+    /// Decodes the escape sequence following a backslash.
+    fn escape(&mut self) -> Option<char> {
+        if self.is_at_end() {
+            return None;
+        }
+
+        let c = self.advance();
+        match c {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'r' => Some('\r'),
+            '\\' | '"' | '$' => Some(c),
+            'u' => self.unicode_escape(),
+            _ => {
+                self.error(format!("Unknown escape sequence: \\{}", c));
+                None
+            }
+        }
     }
 
-    fn number(&mut self) {
-        self.consume_digits();
+    /// Decodes the `{XXXX}` part of a `\u{XXXX}` escape.
+    fn unicode_escape(&mut self) -> Option<char> {
+        if !self.matches('{') {
+            self.error("Expect '{' after '\\u'.".to_string());
+            return None;
+        }
 
-        // Look for a fractional part
-        if self.peek() == '.' && self.peek_next().is_ascii_digit() {
-            // Consume the "."
+        let digits_start = self.current;
+        while self.peek().is_ascii_hexdigit() {
             self.advance();
-            // Consume fractional digits
-            self.consume_digits();
         }
+        let digits = self.source[digits_start..self.current].to_string();
+
+        if !self.matches('}') {
+            self.error("Expect '}' after Unicode escape.".to_string());
+            return None;
+        }
+
+        let c = u32::from_str_radix(&digits, 16)
+            .ok()
+            .filter(|_| digits.len() <= 6)
+            .and_then(char::from_u32);
+        if c.is_none() {
+            self.error(format!("Invalid Unicode escape: \\u{{{}}}", digits));
+        }
+        c
+    }
 
-        let value = &self.source[self.start..self.current];
-        if let Ok(num) = value.parse::<f64>() {
-            self.add_token(TokenType::Number, Literal::Number(num));
+    /// Scans a number literal: decimal with an optional fraction and
+    /// exponent, or hexadecimal (`0x`), binary (`0b`) or octal (`0o`). Digits
+    /// may be grouped with `_` separators. Literals without a fraction or an
+    /// exponent are integers.
+    fn number(&mut self, first: char) {
+        let radix = match (first, self.peek()) {
+            ('0', 'x' | 'X') => 16,
+            ('0', 'b' | 'B') => 2,
+            ('0', 'o' | 'O') => 8,
+            _ => 10,
+        };
+
+        let mut is_float = false;
+        if radix == 10 {
+            self.consume_digits(10);
+
+            // Look for a fractional part
+            if self.peek() == '.' && self.peek_next().is_ascii_digit() {
+                is_float = true;
+                // Consume the "."
+                self.advance();
+                // Consume fractional digits
+                self.consume_digits(10);
+            }
+
+            // Look for an exponent
+            if matches!(self.peek(), 'e' | 'E') {
+                is_float = true;
+                self.advance();
+                if matches!(self.peek(), '+' | '-') {
+                    self.advance();
+                }
+                self.consume_digits(10);
+            }
         } else {
-            self.errors
-                .push(format!("line {}: Invalid number: {}", self.line, value));
+            // Consume the prefix
+            self.advance();
+            self.consume_digits(radix);
+        }
+
+        // Letters or digits running on from the literal belong to it, so that
+        // the whole malformed lexeme is reported.
+        let mut malformed = false;
+        while is_xid_continue(self.peek()) {
+            self.advance();
+            malformed = true;
+        }
+
+        let lexeme = &self.source[self.start..self.current];
+        if malformed || !has_valid_separators(lexeme, radix) {
+            self.error(format!("Invalid number: {}", lexeme));
+            return;
+        }
+
+        let digits: String = lexeme.chars().filter(|&c| c != '_').collect();
+        let value = match radix {
+            10 if is_float => digits.parse::<f64>().ok().map(Literal::Number),
+            10 => digits.parse::<i64>().ok().map(Literal::Integer),
+            _ => i64::from_str_radix(&digits[2..], radix)
+                .ok()
+                .map(Literal::Integer),
+        };
+
+        match value {
+            Some(value) => self.add_token(TokenType::Number, value),
+            // An exponent or a prefix without digits is malformed; any other
+            // failure means the integer does not fit in an i64.
+            None if is_float || (radix != 10 && digits.len() == 2) => {
+                self.error(format!("Invalid number: {}", lexeme))
+            }
+            None => self.error(format!("Integer literal out of range: {}", lexeme)),
         }
     }
 
-    fn consume_digits(&mut self) {
-        while self.peek().is_ascii_digit() {
+    fn consume_digits(&mut self, radix: u32) {
+        while self.peek().is_digit(radix) || self.peek() == '_' {
             self.advance();
         }
     }
 
     fn identifier(&mut self) {
-        while self.peek().is_ascii_alphanumeric() || self.peek() == '_' {
+        while is_xid_continue(self.peek()) {
             self.advance();
         }
 
@@ -212,3 +493,14 @@ impl Scanner {
         self.add_token_type(token_type);
     }
 }
+
+/// Whether every `_` in a number literal sits between two digits.
+fn has_valid_separators(lexeme: &str, radix: u32) -> bool {
+    let chars: Vec<char> = lexeme.chars().collect();
+    chars.iter().enumerate().all(|(i, &c)| {
+        c != '_'
+            || (i > 0
+                && chars[i - 1].is_digit(radix)
+                && chars.get(i + 1).is_some_and(|next| next.is_digit(radix)))
+    })
+}
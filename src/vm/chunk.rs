@@ -0,0 +1,98 @@
+use crate::models::tokens::Span;
+use crate::vm::value::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OpCode {
+    Constant(u16),
+    Nil,
+    True,
+    False,
+    Pop,
+    /// Pushes a copy of the value this many slots below the top.
+    Dup(u8),
+    /// Moves the top of the stack below this many values.
+    Rotate(u8),
+    GetLocal(u8),
+    SetLocal(u8),
+    GetGlobal(u16),
+    DefineGlobal(u16),
+    SetGlobal(u16),
+    GetUpvalue(u8),
+    SetUpvalue(u8),
+    GetProperty(u16),
+    SetProperty(u16),
+    GetSuper(u16),
+    BuildList(u16),
+    BuildMap(u16),
+    /// Joins this many values, turned into text, into one string.
+    Interpolate(u16),
+    GetIndex,
+    SetIndex,
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    IntDivide,
+    Modulo,
+    Power,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    Not,
+    Negate,
+    BitNot,
+    Print,
+    Jump(u16),
+    JumpIfFalse(u16),
+    Loop(u16),
+    /// Replaces the value on top of the stack with an iterator over it.
+    GetIter,
+    /// Pushes the next value of the iterator on top of the stack, or jumps
+    /// forward when it is exhausted.
+    ForNext(u16),
+    Call(u8),
+    /// Calls with this many arguments, the last of which are passed by the
+    /// names in the list constant.
+    CallNamed(u8, u16),
+    /// `Call` and `CallNamed` in tail position: the callee takes over the
+    /// frame of the function making the call.
+    TailCall(u8),
+    TailCallNamed(u8, u16),
+    /// Jumps forward when the caller passed the parameter at this index,
+    /// skipping the code that computes its default.
+    JumpIfPassed(u8, u16),
+    Closure(u16),
+    CloseUpvalue,
+    Return,
+    Class(u16),
+    Inherit,
+    Method(u16),
+}
+
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<OpCode>,
+    pub spans: Vec<Span>,
+    pub constants: Vec<Value>,
+}
+
+impl Chunk {
+    pub fn write(&mut self, op: OpCode, span: Span) -> usize {
+        self.code.push(op);
+        self.spans.push(span);
+        self.code.len() - 1
+    }
+
+    pub fn add_constant(&mut self, value: Value) -> usize {
+        self.constants.push(value);
+        self.constants.len() - 1
+    }
+}
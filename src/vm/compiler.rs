@@ -0,0 +1,954 @@
+use crate::errors::{ParseError, RuntimeError};
+use crate::models::exr::{Expr, ExprVisitor};
+use crate::models::literals::Literal;
+use crate::models::params::{Param, Signature};
+use crate::models::stmt::{Stmt, StmtVisitor};
+use crate::models::token_type::TokenType;
+use crate::models::tokens::{Span, Token};
+use crate::vm::chunk::OpCode;
+use crate::vm::value::{CompiledFunction, UpvalueRef, Value};
+use std::rc::Rc;
+
+const MAX_LOCALS: usize = u8::MAX as usize + 1;
+const MAX_UPVALUES: usize = u8::MAX as usize + 1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FunctionKind {
+    Script,
+    Function,
+    Method,
+    Initializer,
+}
+
+struct Local {
+    name: String,
+    depth: usize,
+    is_captured: bool,
+}
+
+/// Jumps out of a loop body waiting for their targets to be known.
+struct Loop {
+    scope_depth: usize,
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
+struct FunctionState {
+    function: CompiledFunction,
+    kind: FunctionKind,
+    locals: Vec<Local>,
+    scope_depth: usize,
+    loops: Vec<Loop>,
+}
+
+impl FunctionState {
+    fn new(name: String, kind: FunctionKind) -> Self {
+        // Slot zero holds the callee, or the receiver inside methods.
+        let slot_zero = match kind {
+            FunctionKind::Method | FunctionKind::Initializer => "this",
+            _ => "",
+        };
+
+        Self {
+            function: CompiledFunction {
+                name,
+                ..CompiledFunction::default()
+            },
+            kind,
+            locals: vec![Local {
+                name: slot_zero.to_string(),
+                depth: 0,
+                is_captured: false,
+            }],
+            scope_depth: 0,
+            loops: Vec::new(),
+        }
+    }
+}
+
+/// Compiles parsed statements into bytecode for the `Vm`.
+pub struct Compiler {
+    states: Vec<FunctionState>,
+    token: Token,
+    pub errors: Vec<ParseError>,
+}
+
+impl ExprVisitor<()> for Compiler {
+    fn visit_literal_expr(&mut self, literal: &Literal) -> Result<(), RuntimeError> {
+        match literal {
+            Literal::Nil => self.emit(OpCode::Nil),
+            Literal::Boolean(true) => self.emit(OpCode::True),
+            Literal::Boolean(false) => self.emit(OpCode::False),
+            Literal::Integer(n) => self.emit_constant(Value::Integer(*n)),
+            Literal::Number(n) => self.emit_constant(Value::Number(*n)),
+            Literal::String(s) => self.emit_constant(Value::String(Rc::from(s.as_str()))),
+            _ => self.error_at_current(&format!("Can't compile literal {}.", literal)),
+        }
+        Ok(())
+    }
+
+    fn visit_logical_expr(
+        &mut self,
+        left: &Expr,
+        operator: &Token,
+        right: &Expr,
+    ) -> Result<(), RuntimeError> {
+        self.compile_expr(left);
+        self.token = operator.clone();
+
+        if operator.token_type == TokenType::And {
+            let end_jump = self.emit_jump(OpCode::JumpIfFalse(0));
+            self.emit(OpCode::Pop);
+            self.compile_expr(right);
+            self.patch_jump(end_jump);
+        } else {
+            let else_jump = self.emit_jump(OpCode::JumpIfFalse(0));
+            let end_jump = self.emit_jump(OpCode::Jump(0));
+            self.patch_jump(else_jump);
+            self.emit(OpCode::Pop);
+            self.compile_expr(right);
+            self.patch_jump(end_jump);
+        }
+        Ok(())
+    }
+
+    fn visit_binary_expr(
+        &mut self,
+        left: &Expr,
+        operator: &Token,
+        right: &Expr,
+    ) -> Result<(), RuntimeError> {
+        self.compile_expr(left);
+        self.compile_expr(right);
+        self.binary_op(operator);
+        Ok(())
+    }
+
+    fn visit_call_expr(
+        &mut self,
+        callee: &Expr,
+        paren: &Token,
+        arguments: &[Expr],
+        named: &[(Token, Expr)],
+    ) -> Result<(), RuntimeError> {
+        self.compile_expr(callee);
+        for argument in arguments {
+            self.compile_expr(argument);
+        }
+        for (_, argument) in named {
+            self.compile_expr(argument);
+        }
+
+        self.token = paren.clone();
+        let arg_count = (arguments.len() + named.len()) as u8;
+        if named.is_empty() {
+            self.emit(OpCode::Call(arg_count));
+        } else {
+            let names = named
+                .iter()
+                .map(|(name, _)| Value::String(Rc::from(name.lexeme.as_str())))
+                .collect();
+            let names = self.make_constant(Value::list(names));
+            self.emit(OpCode::CallNamed(arg_count, names));
+        }
+        Ok(())
+    }
+
+    fn visit_grouping_expr(&mut self, expression: &Expr) -> Result<(), RuntimeError> {
+        self.compile_expr(expression);
+        Ok(())
+    }
+
+    fn visit_unary_expr(&mut self, operator: &Token, right: &Expr) -> Result<(), RuntimeError> {
+        self.compile_expr(right);
+        self.token = operator.clone();
+
+        match operator.token_type {
+            TokenType::Minus => self.emit(OpCode::Negate),
+            TokenType::Bang => self.emit(OpCode::Not),
+            TokenType::Tilde => self.emit(OpCode::BitNot),
+            _ => self.error(operator, "Unknown unary operator."),
+        }
+        Ok(())
+    }
+
+    fn visit_variable_expr(&mut self, token: &Token, _id: usize) -> Result<(), RuntimeError> {
+        self.get_variable(token);
+        Ok(())
+    }
+
+    fn visit_assign_expr(
+        &mut self,
+        token: &Token,
+        expr: &Expr,
+        _id: usize,
+    ) -> Result<(), RuntimeError> {
+        self.compile_expr(expr);
+        self.set_variable(token);
+        Ok(())
+    }
+
+    fn visit_get_expr(&mut self, object: &Expr, name: &Token) -> Result<(), RuntimeError> {
+        self.compile_expr(object);
+        self.token = name.clone();
+
+        let constant = self.identifier_constant(name);
+        self.emit(OpCode::GetProperty(constant));
+        Ok(())
+    }
+
+    fn visit_set_expr(
+        &mut self,
+        object: &Expr,
+        name: &Token,
+        value: &Expr,
+    ) -> Result<(), RuntimeError> {
+        self.compile_expr(object);
+        self.compile_expr(value);
+        self.token = name.clone();
+
+        let constant = self.identifier_constant(name);
+        self.emit(OpCode::SetProperty(constant));
+        Ok(())
+    }
+
+    fn visit_list_expr(&mut self, bracket: &Token, elements: &[Expr]) -> Result<(), RuntimeError> {
+        for element in elements {
+            self.compile_expr(element);
+        }
+
+        self.token = bracket.clone();
+        match u16::try_from(elements.len()) {
+            Ok(count) => self.emit(OpCode::BuildList(count)),
+            Err(_) => self.error(bracket, "Too many elements in a list literal."),
+        }
+        Ok(())
+    }
+
+    fn visit_interpolation_expr(
+        &mut self,
+        token: &Token,
+        parts: &[Expr],
+    ) -> Result<(), RuntimeError> {
+        for part in parts {
+            self.compile_expr(part);
+        }
+
+        self.token = token.clone();
+        match u16::try_from(parts.len()) {
+            Ok(count) => self.emit(OpCode::Interpolate(count)),
+            Err(_) => self.error(token, "Too many parts in a string interpolation."),
+        }
+        Ok(())
+    }
+
+    fn visit_map_expr(
+        &mut self,
+        brace: &Token,
+        entries: &[(Expr, Expr)],
+    ) -> Result<(), RuntimeError> {
+        for (key, value) in entries {
+            self.compile_expr(key);
+            self.compile_expr(value);
+        }
+
+        self.token = brace.clone();
+        match u16::try_from(entries.len()) {
+            Ok(count) => self.emit(OpCode::BuildMap(count)),
+            Err(_) => self.error(brace, "Too many entries in a map literal."),
+        }
+        Ok(())
+    }
+
+    fn visit_index_expr(
+        &mut self,
+        object: &Expr,
+        bracket: &Token,
+        index: &Expr,
+    ) -> Result<(), RuntimeError> {
+        self.compile_expr(object);
+        self.compile_expr(index);
+        self.token = bracket.clone();
+        self.emit(OpCode::GetIndex);
+        Ok(())
+    }
+
+    fn visit_set_index_expr(
+        &mut self,
+        object: &Expr,
+        bracket: &Token,
+        index: &Expr,
+        value: &Expr,
+    ) -> Result<(), RuntimeError> {
+        self.compile_expr(object);
+        self.compile_expr(index);
+        self.compile_expr(value);
+        self.token = bracket.clone();
+        self.emit(OpCode::SetIndex);
+        Ok(())
+    }
+
+    fn visit_compound_expr(
+        &mut self,
+        target: &Expr,
+        operator: &Token,
+        value: &Expr,
+        postfix: bool,
+    ) -> Result<(), RuntimeError> {
+        match target {
+            Expr::Variable(token, _) => {
+                self.get_variable(token);
+                if postfix {
+                    self.emit(OpCode::Dup(0));
+                }
+                self.compile_expr(value);
+                self.binary_op(operator);
+                self.set_variable(token);
+            }
+            Expr::Get(object, name) => {
+                // The object stays on the stack for the `SetProperty`; a
+                // postfix update tucks the old value beneath it.
+                self.compile_expr(object);
+                self.token = name.clone();
+                let constant = self.identifier_constant(name);
+                self.emit(OpCode::Dup(0));
+                self.emit(OpCode::GetProperty(constant));
+                if postfix {
+                    self.emit(OpCode::Dup(0));
+                    self.emit(OpCode::Rotate(2));
+                }
+                self.compile_expr(value);
+                self.binary_op(operator);
+                self.token = name.clone();
+                self.emit(OpCode::SetProperty(constant));
+            }
+            Expr::Index(object, bracket, index) => {
+                self.compile_expr(object);
+                self.compile_expr(index);
+                self.token = bracket.clone();
+                self.emit(OpCode::Dup(1));
+                self.emit(OpCode::Dup(1));
+                self.emit(OpCode::GetIndex);
+                if postfix {
+                    self.emit(OpCode::Dup(0));
+                    self.emit(OpCode::Rotate(3));
+                }
+                self.compile_expr(value);
+                self.binary_op(operator);
+                self.token = bracket.clone();
+                self.emit(OpCode::SetIndex);
+            }
+            _ => {
+                self.error(operator, "Invalid assignment target.");
+                return Ok(());
+            }
+        }
+
+        if postfix {
+            self.emit(OpCode::Pop);
+        }
+        Ok(())
+    }
+
+    fn visit_lambda_expr(
+        &mut self,
+        name: &Token,
+        params: &[Param],
+        body: &Rc<[Stmt]>,
+    ) -> Result<(), RuntimeError> {
+        self.token = name.clone();
+        self.function(name, params, body, FunctionKind::Function);
+        Ok(())
+    }
+
+    fn visit_this_expr(&mut self, keyword: &Token, _id: usize) -> Result<(), RuntimeError> {
+        self.get_variable(keyword);
+        Ok(())
+    }
+
+    fn visit_super_expr(
+        &mut self,
+        keyword: &Token,
+        method: &Token,
+        _id: usize,
+    ) -> Result<(), RuntimeError> {
+        self.token = keyword.clone();
+        let this = self.synthetic_token("this");
+        let superclass = self.synthetic_token("super");
+        self.get_variable(&this);
+        self.get_variable(&superclass);
+
+        let constant = self.identifier_constant(method);
+        self.emit(OpCode::GetSuper(constant));
+        Ok(())
+    }
+}
+
+impl StmtVisitor<()> for Compiler {
+    fn visit_expr_stmt(&mut self, expr: &Expr) {
+        self.compile_expr(expr);
+        self.emit(OpCode::Pop);
+    }
+
+    fn visit_class_stmt(&mut self, name: &Token, superclass: &Option<Expr>, methods: &[Stmt]) {
+        self.token = name.clone();
+
+        let constant = self.identifier_constant(name);
+        self.emit(OpCode::Class(constant));
+        self.define_variable(name);
+
+        if let Some(superclass) = superclass {
+            self.compile_expr(superclass);
+
+            self.begin_scope();
+            let superclass = self.synthetic_token("super");
+            self.add_local(&superclass);
+
+            self.get_variable(name);
+            self.emit(OpCode::Inherit);
+        }
+
+        self.get_variable(name);
+        for method in methods {
+            if let Stmt::Function(method_name, params, body, _) = method {
+                let kind = if method_name.lexeme == "init" {
+                    FunctionKind::Initializer
+                } else {
+                    FunctionKind::Method
+                };
+                self.function(method_name, params, body, kind);
+
+                let constant = self.identifier_constant(method_name);
+                self.emit(OpCode::Method(constant));
+            }
+        }
+        self.emit(OpCode::Pop);
+
+        if superclass.is_some() {
+            self.end_scope();
+        }
+    }
+
+    fn visit_function_stmt(&mut self, name: &Token, params: &[Param], body: &Rc<[Stmt]>) {
+        self.token = name.clone();
+
+        if self.current().scope_depth > 0 {
+            // Declared before the body so the function can refer to itself.
+            self.add_local(name);
+            self.function(name, params, body, FunctionKind::Function);
+        } else {
+            self.function(name, params, body, FunctionKind::Function);
+            self.define_variable(name);
+        }
+    }
+
+    fn visit_if_stmt(&mut self, cond: &Expr, then_branch: &Stmt, else_branch: &Option<Box<Stmt>>) {
+        self.compile_expr(cond);
+
+        let then_jump = self.emit_jump(OpCode::JumpIfFalse(0));
+        self.emit(OpCode::Pop);
+        self.compile_stmt(then_branch);
+
+        let else_jump = self.emit_jump(OpCode::Jump(0));
+        self.patch_jump(then_jump);
+        self.emit(OpCode::Pop);
+
+        if let Some(else_branch) = else_branch {
+            self.compile_stmt(else_branch);
+        }
+        self.patch_jump(else_jump);
+    }
+
+    fn visit_print_stmt(&mut self, expr: &Expr) {
+        self.compile_expr(expr);
+        self.emit(OpCode::Print);
+    }
+
+    fn visit_while_stmt(&mut self, cond: &Expr, body: &Stmt, increment: &Option<Expr>) {
+        let loop_start = self.current().function.chunk.code.len();
+        self.compile_expr(cond);
+
+        let exit_jump = self.emit_jump(OpCode::JumpIfFalse(0));
+        self.emit(OpCode::Pop);
+
+        let scope_depth = self.current().scope_depth;
+        self.current_mut().loops.push(Loop {
+            scope_depth,
+            breaks: Vec::new(),
+            continues: Vec::new(),
+        });
+        self.compile_stmt(body);
+        let jumps = self.current_mut().loops.pop().expect("loop state");
+
+        for jump in jumps.continues {
+            self.patch_jump(jump);
+        }
+        if let Some(increment) = increment {
+            self.compile_expr(increment);
+            self.emit(OpCode::Pop);
+        }
+        self.emit_loop(loop_start);
+
+        self.patch_jump(exit_jump);
+        self.emit(OpCode::Pop);
+        for jump in jumps.breaks {
+            self.patch_jump(jump);
+        }
+    }
+
+    fn visit_for_in_stmt(&mut self, name: &Token, iterable: &Expr, body: &Stmt) {
+        self.compile_expr(iterable);
+        self.token = name.clone();
+        self.emit(OpCode::GetIter);
+
+        // The iterator stays in a hidden local for the whole loop. The space
+        // keeps its name from clashing with any identifier.
+        self.begin_scope();
+        let iterator = self.synthetic_token(" iterator");
+        self.add_local(&iterator);
+
+        let loop_start = self.current().function.chunk.code.len();
+        self.token = name.clone();
+        let exit_jump = self.emit_jump(OpCode::ForNext(0));
+
+        let scope_depth = self.current().scope_depth;
+        self.current_mut().loops.push(Loop {
+            scope_depth,
+            breaks: Vec::new(),
+            continues: Vec::new(),
+        });
+
+        // A scope per pass, so closures capture a fresh loop variable.
+        self.begin_scope();
+        self.add_local(name);
+        self.compile_stmt(body);
+        self.end_scope();
+
+        let jumps = self.current_mut().loops.pop().expect("loop state");
+        for jump in jumps.continues {
+            self.patch_jump(jump);
+        }
+        self.emit_loop(loop_start);
+
+        self.patch_jump(exit_jump);
+        for jump in jumps.breaks {
+            self.patch_jump(jump);
+        }
+        self.end_scope();
+    }
+
+    fn visit_break_stmt(&mut self, keyword: &Token) {
+        self.token = keyword.clone();
+        self.discard_loop_locals();
+
+        let jump = self.emit_jump(OpCode::Jump(0));
+        if let Some(innermost) = self.current_mut().loops.last_mut() {
+            innermost.breaks.push(jump);
+        }
+    }
+
+    fn visit_continue_stmt(&mut self, keyword: &Token) {
+        self.token = keyword.clone();
+        self.discard_loop_locals();
+
+        let jump = self.emit_jump(OpCode::Jump(0));
+        if let Some(innermost) = self.current_mut().loops.last_mut() {
+            innermost.continues.push(jump);
+        }
+    }
+
+    fn visit_return_stmt(&mut self, token: &Token, expr: &Option<Expr>) {
+        self.token = token.clone();
+
+        if self.current().kind == FunctionKind::Initializer {
+            self.emit(OpCode::GetLocal(0));
+        } else {
+            match expr {
+                Some(expr) => {
+                    self.compile_expr(expr);
+                    if matches!(expr, Expr::Call(..)) {
+                        self.mark_tail_call();
+                    }
+                }
+                None => self.emit(OpCode::Nil),
+            }
+        }
+        self.emit(OpCode::Return);
+    }
+
+    fn visit_var_stmt(&mut self, name: &Token, expr: &Option<Expr>) {
+        self.token = name.clone();
+
+        match expr {
+            Some(expr) => self.compile_expr(expr),
+            None => self.emit(OpCode::Nil),
+        }
+        self.define_variable(name);
+    }
+
+    fn visit_block_stmt(&mut self, stmts: &[Stmt]) {
+        self.begin_scope();
+        for stmt in stmts {
+            self.compile_stmt(stmt);
+        }
+        self.end_scope();
+    }
+}
+
+impl Compiler {
+    pub fn new() -> Self {
+        Self {
+            states: Vec::new(),
+            token: Token::new(
+                TokenType::Eof,
+                String::new(),
+                Literal::Nil,
+                Span::from_line(1),
+            ),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Compiles a single top-level statement into a script function. An
+    /// expression statement returns its value instead of discarding it.
+    pub fn compile(&mut self, stmt: &Stmt) -> Rc<CompiledFunction> {
+        self.states.push(FunctionState::new(
+            "script".to_string(),
+            FunctionKind::Script,
+        ));
+
+        match stmt {
+            Stmt::Expr(expr) => {
+                self.compile_expr(expr);
+                self.emit(OpCode::Return);
+            }
+            _ => {
+                self.compile_stmt(stmt);
+                self.emit_return();
+            }
+        }
+
+        let state = self.states.pop().expect("script state");
+        Rc::new(state.function)
+    }
+
+    fn compile_stmt(&mut self, stmt: &Stmt) {
+        stmt.accept(self);
+    }
+
+    /// Emits the instruction for a binary operator whose operands are
+    /// already on the stack.
+    fn binary_op(&mut self, operator: &Token) {
+        self.token = operator.clone();
+
+        let op = match operator.token_type {
+            TokenType::Plus => OpCode::Add,
+            TokenType::Minus => OpCode::Subtract,
+            TokenType::Star => OpCode::Multiply,
+            TokenType::Slash => OpCode::Divide,
+            TokenType::TildeSlash => OpCode::IntDivide,
+            TokenType::Percent => OpCode::Modulo,
+            TokenType::StarStar => OpCode::Power,
+            TokenType::Ampersand => OpCode::BitAnd,
+            TokenType::Pipe => OpCode::BitOr,
+            TokenType::Caret => OpCode::BitXor,
+            TokenType::LessLess => OpCode::ShiftLeft,
+            TokenType::GreaterGreater => OpCode::ShiftRight,
+            TokenType::Greater => OpCode::Greater,
+            TokenType::GreaterEqual => OpCode::GreaterEqual,
+            TokenType::Less => OpCode::Less,
+            TokenType::LessEqual => OpCode::LessEqual,
+            TokenType::EqualEqual => OpCode::Equal,
+            TokenType::BangEqual => OpCode::NotEqual,
+            _ => {
+                self.error(operator, "Unknown binary operator.");
+                return;
+            }
+        };
+        self.emit(op);
+    }
+
+    fn compile_expr(&mut self, expr: &Expr) {
+        // Compile errors are collected in `self.errors`, never through the result.
+        let _ = expr.accept(self);
+    }
+
+    fn function(&mut self, name: &Token, params: &[Param], body: &[Stmt], kind: FunctionKind) {
+        self.states
+            .push(FunctionState::new(name.lexeme.clone(), kind));
+        self.current_mut().function.signature = Signature::new(params);
+        self.begin_scope();
+
+        for (index, param) in params.iter().enumerate() {
+            // Compiled before the parameter is declared, so that a default
+            // sees the parameters before it, as in the resolver.
+            if let Some(default) = &param.default {
+                let skip = self.emit_jump(OpCode::JumpIfPassed(index as u8, 0));
+                self.compile_expr(default);
+                self.emit(OpCode::SetLocal(index as u8 + 1));
+                self.emit(OpCode::Pop);
+                self.patch_jump(skip);
+            }
+            self.add_local(&param.name);
+        }
+
+        for stmt in body {
+            self.compile_stmt(stmt);
+        }
+        self.emit_return();
+
+        let state = self.states.pop().expect("function state");
+        let constant = self.make_constant(Value::Function(Rc::new(state.function)));
+        self.emit(OpCode::Closure(constant));
+    }
+
+    fn current(&self) -> &FunctionState {
+        self.states.last().expect("no function is being compiled")
+    }
+
+    fn current_mut(&mut self) -> &mut FunctionState {
+        self.states
+            .last_mut()
+            .expect("no function is being compiled")
+    }
+
+    fn begin_scope(&mut self) {
+        self.current_mut().scope_depth += 1;
+    }
+
+    fn end_scope(&mut self) {
+        self.current_mut().scope_depth -= 1;
+
+        loop {
+            let state = self.current();
+            let Some(local) = state.locals.last() else {
+                break;
+            };
+            if local.depth <= state.scope_depth {
+                break;
+            }
+
+            let op = if local.is_captured {
+                OpCode::CloseUpvalue
+            } else {
+                OpCode::Pop
+            };
+            self.emit(op);
+            self.current_mut().locals.pop();
+        }
+    }
+
+    /// Pops the locals declared inside the innermost loop body before a
+    /// `break` or `continue` jumps out of it. The compiler keeps tracking
+    /// them, since the code after the jump is still in their scope.
+    fn discard_loop_locals(&mut self) {
+        let state = self.current();
+        let Some(innermost) = state.loops.last() else {
+            return;
+        };
+
+        let ops: Vec<OpCode> = state
+            .locals
+            .iter()
+            .rev()
+            .take_while(|local| local.depth > innermost.scope_depth)
+            .map(|local| match local.is_captured {
+                true => OpCode::CloseUpvalue,
+                false => OpCode::Pop,
+            })
+            .collect();
+
+        for op in ops {
+            self.emit(op);
+        }
+    }
+
+    fn add_local(&mut self, name: &Token) {
+        if self.current().locals.len() >= MAX_LOCALS {
+            self.error(name, "Too many local variables in function.");
+            return;
+        }
+
+        let depth = self.current().scope_depth;
+        self.current_mut().locals.push(Local {
+            name: name.lexeme.clone(),
+            depth,
+            is_captured: false,
+        });
+    }
+
+    fn define_variable(&mut self, name: &Token) {
+        if self.current().scope_depth > 0 {
+            self.add_local(name);
+        } else {
+            let constant = self.identifier_constant(name);
+            self.emit(OpCode::DefineGlobal(constant));
+        }
+    }
+
+    fn get_variable(&mut self, name: &Token) {
+        self.token = name.clone();
+        let top = self.states.len() - 1;
+
+        let op = if let Some(slot) = self.resolve_local(top, &name.lexeme) {
+            OpCode::GetLocal(slot)
+        } else if let Some(index) = self.resolve_upvalue(top, name) {
+            OpCode::GetUpvalue(index)
+        } else {
+            OpCode::GetGlobal(self.identifier_constant(name))
+        };
+        self.emit(op);
+    }
+
+    fn set_variable(&mut self, name: &Token) {
+        self.token = name.clone();
+        let top = self.states.len() - 1;
+
+        let op = if let Some(slot) = self.resolve_local(top, &name.lexeme) {
+            OpCode::SetLocal(slot)
+        } else if let Some(index) = self.resolve_upvalue(top, name) {
+            OpCode::SetUpvalue(index)
+        } else {
+            OpCode::SetGlobal(self.identifier_constant(name))
+        };
+        self.emit(op);
+    }
+
+    fn resolve_local(&self, state: usize, name: &str) -> Option<u8> {
+        self.states[state]
+            .locals
+            .iter()
+            .rposition(|local| local.name == name)
+            .map(|slot| slot as u8)
+    }
+
+    fn resolve_upvalue(&mut self, state: usize, name: &Token) -> Option<u8> {
+        if state == 0 {
+            return None;
+        }
+
+        if let Some(slot) = self.resolve_local(state - 1, &name.lexeme) {
+            self.states[state - 1].locals[slot as usize].is_captured = true;
+            return self.add_upvalue(state, name, slot, true);
+        }
+
+        let index = self.resolve_upvalue(state - 1, name)?;
+        self.add_upvalue(state, name, index, false)
+    }
+
+    fn add_upvalue(&mut self, state: usize, name: &Token, index: u8, is_local: bool) -> Option<u8> {
+        let upvalue = UpvalueRef { is_local, index };
+        let upvalues = &mut self.states[state].function.upvalues;
+
+        if let Some(existing) = upvalues.iter().position(|u| *u == upvalue) {
+            return Some(existing as u8);
+        }
+
+        if upvalues.len() >= MAX_UPVALUES {
+            self.error(name, "Too many closure variables in function.");
+            return None;
+        }
+
+        upvalues.push(upvalue);
+        Some((upvalues.len() - 1) as u8)
+    }
+
+    fn identifier_constant(&mut self, name: &Token) -> u16 {
+        self.make_constant(Value::String(Rc::from(name.lexeme.as_str())))
+    }
+
+    fn make_constant(&mut self, value: Value) -> u16 {
+        let index = self.current_mut().function.chunk.add_constant(value);
+        match u16::try_from(index) {
+            Ok(index) => index,
+            Err(_) => {
+                self.error_at_current("Too many constants in one chunk.");
+                0
+            }
+        }
+    }
+
+    fn emit(&mut self, op: OpCode) {
+        let span = self.token.span;
+        self.current_mut().function.chunk.write(op, span);
+    }
+
+    fn emit_constant(&mut self, value: Value) {
+        let constant = self.make_constant(value);
+        self.emit(OpCode::Constant(constant));
+    }
+
+    fn emit_return(&mut self) {
+        if self.current().kind == FunctionKind::Initializer {
+            self.emit(OpCode::GetLocal(0));
+        } else {
+            self.emit(OpCode::Nil);
+        }
+        self.emit(OpCode::Return);
+    }
+
+    fn emit_jump(&mut self, op: OpCode) -> usize {
+        let span = self.token.span;
+        self.current_mut().function.chunk.write(op, span)
+    }
+
+    fn patch_jump(&mut self, index: usize) {
+        let code = &self.current().function.chunk.code;
+        let Ok(offset) = u16::try_from(code.len() - index - 1) else {
+            self.error_at_current("Too much code to jump over.");
+            return;
+        };
+
+        let code = &mut self.current_mut().function.chunk.code;
+        code[index] = match code[index] {
+            OpCode::Jump(_) => OpCode::Jump(offset),
+            OpCode::JumpIfFalse(_) => OpCode::JumpIfFalse(offset),
+            OpCode::ForNext(_) => OpCode::ForNext(offset),
+            OpCode::JumpIfPassed(param, _) => OpCode::JumpIfPassed(param, offset),
+            op => op,
+        };
+    }
+
+    /// Turns the call just emitted into a tail call. The `Return` after it
+    /// still runs when the callee finishes without a frame of its own, as
+    /// natives do.
+    fn mark_tail_call(&mut self) {
+        let code = &mut self.current_mut().function.chunk.code;
+        if let Some(op) = code.last_mut() {
+            *op = match *op {
+                OpCode::Call(arg_count) => OpCode::TailCall(arg_count),
+                OpCode::CallNamed(arg_count, names) => OpCode::TailCallNamed(arg_count, names),
+                op => op,
+            };
+        }
+    }
+
+    fn emit_loop(&mut self, loop_start: usize) {
+        let code = &self.current().function.chunk.code;
+        match u16::try_from(code.len() + 1 - loop_start) {
+            Ok(offset) => self.emit(OpCode::Loop(offset)),
+            Err(_) => self.error_at_current("Loop body too large."),
+        }
+    }
+
+    fn synthetic_token(&self, lexeme: &str) -> Token {
+        Token::new(
+            TokenType::Identifier,
+            lexeme.to_string(),
+            Literal::Nil,
+            self.token.span,
+        )
+    }
+
+    fn error(&mut self, token: &Token, message: &str) {
+        self.errors.push(ParseError {
+            token: token.clone(),
+            message: message.to_string(),
+        });
+    }
+
+    fn error_at_current(&mut self, message: &str) {
+        let token = self.token.clone();
+        self.error(&token, message);
+    }
+}
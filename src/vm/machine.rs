@@ -0,0 +1,972 @@
+use crate::errors::{Frame, ParseError, RuntimeError};
+use crate::models::collections;
+use crate::models::constants::{MAX_CALL_DEPTH, MAX_STACK_USE};
+use crate::models::literals::Literal;
+use crate::models::natives;
+use crate::models::numbers::{self, ArithmeticOp, BitwiseOp};
+use crate::models::params::{self, Binding, Signature};
+use crate::models::stack::StackUse;
+use crate::models::stmt::Stmt;
+use crate::models::token_type::TokenType;
+use crate::models::tokens::{Span, Token};
+use crate::vm::chunk::OpCode;
+use crate::vm::compiler::Compiler;
+use crate::vm::value::{
+    BoundMethod, ClassObject, Closure, CompiledFunction, InstanceObject, IteratorObject,
+    NativeFunction, Upvalue, Value,
+};
+use indexmap::IndexMap;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+struct CallFrame {
+    closure: Rc<Closure>,
+    ip: usize,
+    slots: usize,
+    // Whether the caller passed each parameter, by index. Empty when it
+    // passed all of them, which is the common case.
+    passed: Vec<bool>,
+}
+
+/// Stack-based virtual machine running bytecode produced by the `Compiler`.
+pub struct Vm {
+    pub errors: Vec<RuntimeError>,
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    globals: HashMap<String, Value>,
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    max_call_depth: usize,
+    stack_use: StackUse,
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        let mut vm = Self {
+            errors: Vec::new(),
+            stack: Vec::new(),
+            frames: Vec::new(),
+            globals: HashMap::new(),
+            open_upvalues: Vec::new(),
+            max_call_depth: MAX_CALL_DEPTH,
+            stack_use: StackUse::new(MAX_STACK_USE),
+        };
+
+        vm.define_builtins();
+        vm
+    }
+
+    fn define_builtins(&mut self) {
+        for (name, arity, body) in natives::builtins::<Value>() {
+            self.define_native(name, arity, body);
+        }
+    }
+
+    /// Compiles every top-level statement into its own script, so a runtime
+    /// error skips only the statement that raised it, as in the `Interpreter`.
+    pub fn compile(stmts: &[Stmt]) -> Result<Vec<Rc<CompiledFunction>>, Vec<ParseError>> {
+        let mut compiler = Compiler::new();
+        let scripts = stmts.iter().map(|stmt| compiler.compile(stmt)).collect();
+
+        if compiler.errors.is_empty() {
+            Ok(scripts)
+        } else {
+            Err(compiler.errors)
+        }
+    }
+
+    /// Runs compiled scripts and returns the value of the last one, which is
+    /// `nil` unless it was an expression statement.
+    pub fn interpret(&mut self, scripts: &[Rc<CompiledFunction>]) -> Literal {
+        let mut last = Literal::Nil;
+
+        for script in scripts {
+            last = match self.run_script(Rc::clone(script)) {
+                Ok(value) => value.to_literal(),
+                Err(error) => {
+                    let error = error.traced(|| self.trace(true));
+                    self.errors.push(error);
+                    self.reset();
+                    Literal::Nil
+                }
+            };
+        }
+
+        last
+    }
+
+    /// Sets how deeply Lox calls may nest before they fail with a stack
+    /// overflow.
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.max_call_depth = depth;
+    }
+
+    /// Sets how much native stack nested runs of the VM, such as iterator
+    /// methods called by for-in loops, may use.
+    pub fn set_max_stack_use(&mut self, bytes: usize) {
+        self.stack_use.set_limit(bytes);
+    }
+
+    pub fn define_global(&mut self, name: &str, value: Value) {
+        self.globals.insert(name.to_string(), value);
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.globals.get(name).cloned()
+    }
+
+    /// Calls a Lox function or class from Rust.
+    pub fn call_function(
+        &mut self,
+        callee: Value,
+        args: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        let base = self.frames.len();
+        let arg_count = args.len();
+
+        self.push(callee.clone());
+        self.stack.extend(args);
+
+        let result = self.call_value(callee, arg_count, &[]).and_then(|()| {
+            if self.frames.len() > base {
+                self.run(base)
+            } else {
+                // Natives and classes without an initializer finish right away.
+                Ok(self.pop())
+            }
+        });
+
+        result.map_err(|error| {
+            let error = error.traced(|| self.trace(false));
+            self.reset();
+            error
+        })
+    }
+
+    fn define_native(
+        &mut self,
+        name: &str,
+        arity: usize,
+        function: fn(&[Value]) -> Result<Value, String>,
+    ) {
+        let native = Value::Native(Rc::new(NativeFunction {
+            name: name.to_string(),
+            arity,
+            function,
+        }));
+        self.globals.insert(name.to_string(), native);
+    }
+
+    /// The calls in progress, innermost first. The bottom frame is the
+    /// script's own when running one rather than a call from Rust. Errors
+    /// raised in the script itself get no trace.
+    fn trace(&self, in_script: bool) -> Vec<Frame> {
+        if in_script && self.frames.len() <= 1 {
+            return Vec::new();
+        }
+        self.frames
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(index, frame)| Frame {
+                function: (!in_script || index > 0).then(|| frame.closure.function.name.clone()),
+                line: frame.closure.function.chunk.spans[frame.ip.saturating_sub(1)].line,
+            })
+            .collect()
+    }
+
+    fn reset(&mut self) {
+        // Closures that escaped before the error must keep their values.
+        self.close_upvalues(0);
+        self.stack.clear();
+        self.frames.clear();
+        self.open_upvalues.clear();
+    }
+
+    fn run_script(&mut self, script: Rc<CompiledFunction>) -> Result<Value, RuntimeError> {
+        let closure = Rc::new(Closure {
+            function: script,
+            upvalues: Vec::new(),
+        });
+
+        self.stack.push(Value::Closure(Rc::clone(&closure)));
+        self.call(closure, 0, &[])?;
+        self.run(0)
+    }
+
+    /// Runs until the frame count drops back to `base`, returning the value
+    /// produced by the frame that returned last.
+    fn run(&mut self, base: usize) -> Result<Value, RuntimeError> {
+        loop {
+            let op = {
+                let frame = self.frame_mut();
+                let op = frame.closure.function.chunk.code[frame.ip];
+                frame.ip += 1;
+                op
+            };
+
+            match op {
+                OpCode::Constant(index) => {
+                    let constant = self.read_constant(index);
+                    self.push(constant);
+                }
+                OpCode::Nil => self.push(Value::Nil),
+                OpCode::True => self.push(Value::Boolean(true)),
+                OpCode::False => self.push(Value::Boolean(false)),
+                OpCode::Pop => {
+                    self.pop();
+                }
+                OpCode::Dup(distance) => self.push(self.peek(distance as usize).clone()),
+                OpCode::Rotate(depth) => {
+                    let value = self.pop();
+                    let index = self.stack.len() - depth as usize;
+                    self.stack.insert(index, value);
+                }
+                OpCode::GetLocal(slot) => {
+                    let value = self.stack[self.frame().slots + slot as usize].clone();
+                    self.push(value);
+                }
+                OpCode::SetLocal(slot) => {
+                    let index = self.frame().slots + slot as usize;
+                    self.stack[index] = self.peek(0).clone();
+                }
+                OpCode::GetGlobal(index) => {
+                    let name = self.read_string(index);
+                    match self.globals.get(name.as_ref()) {
+                        Some(value) => {
+                            let value = value.clone();
+                            self.push(value);
+                        }
+                        None => return Err(self.undefined_variable(&name)),
+                    }
+                }
+                OpCode::DefineGlobal(index) => {
+                    let name = self.read_string(index);
+                    let value = self.pop();
+                    self.globals.insert(name.to_string(), value);
+                }
+                OpCode::SetGlobal(index) => {
+                    let name = self.read_string(index);
+                    let value = self.peek(0).clone();
+                    match self.globals.get_mut(name.as_ref()) {
+                        Some(slot) => *slot = value,
+                        None => return Err(self.undefined_variable(&name)),
+                    }
+                }
+                OpCode::GetUpvalue(index) => {
+                    let upvalue = Rc::clone(&self.frame().closure.upvalues[index as usize]);
+                    let value = match &*upvalue.borrow() {
+                        Upvalue::Open(slot) => self.stack[*slot].clone(),
+                        Upvalue::Closed(value) => value.clone(),
+                    };
+                    self.push(value);
+                }
+                OpCode::SetUpvalue(index) => {
+                    let upvalue = Rc::clone(&self.frame().closure.upvalues[index as usize]);
+                    let value = self.peek(0).clone();
+                    let mut upvalue = upvalue.borrow_mut();
+                    match &mut *upvalue {
+                        Upvalue::Open(slot) => self.stack[*slot] = value,
+                        Upvalue::Closed(closed) => *closed = value,
+                    }
+                }
+                OpCode::GetProperty(index) => {
+                    let name = self.read_string(index);
+                    let Value::Instance(instance) = self.peek(0).clone() else {
+                        return Err(self.type_error("Only instances have properties."));
+                    };
+
+                    let field = instance.borrow().fields.get(name.as_ref()).cloned();
+                    match field {
+                        Some(value) => {
+                            self.pop();
+                            self.push(value);
+                        }
+                        None => {
+                            let class = Rc::clone(&instance.borrow().class);
+                            self.bind_method(&class, &name)?;
+                        }
+                    }
+                }
+                OpCode::SetProperty(index) => {
+                    let name = self.read_string(index);
+                    let Value::Instance(instance) = self.peek(1).clone() else {
+                        return Err(self.type_error("Only instances have fields."));
+                    };
+
+                    let value = self.pop();
+                    instance
+                        .borrow_mut()
+                        .fields
+                        .insert(name.to_string(), value.clone());
+                    self.pop();
+                    self.push(value);
+                }
+                OpCode::BuildList(count) => {
+                    let start = self.stack.len() - count as usize;
+                    let elements = self.stack.split_off(start);
+                    self.push(Value::list(elements));
+                }
+                OpCode::Interpolate(count) => {
+                    let start = self.stack.len() - count as usize;
+                    let text: String = self
+                        .stack
+                        .split_off(start)
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect();
+                    self.push(Value::String(Rc::from(text)));
+                }
+                OpCode::BuildMap(count) => {
+                    let start = self.stack.len() - 2 * count as usize;
+                    let mut map = IndexMap::with_capacity(count as usize);
+                    for pair in self.stack.split_off(start).chunks_exact(2) {
+                        if !pair[0].is_key() {
+                            return Err(self.type_error(&collections::invalid_key(&pair[0])));
+                        }
+                        map.insert(pair[0].clone(), pair[1].clone());
+                    }
+                    self.push(Value::map(map));
+                }
+                OpCode::GetIndex => {
+                    let (object, index) = self.pop_pair();
+                    let element = self.get_index(&object, &index)?;
+                    self.push(element);
+                }
+                OpCode::SetIndex => {
+                    let value = self.pop();
+                    let (object, index) = self.pop_pair();
+                    self.set_index(&object, &index, value.clone())?;
+                    self.push(value);
+                }
+                OpCode::GetSuper(index) => {
+                    let name = self.read_string(index);
+                    let Value::Class(superclass) = self.pop() else {
+                        return Err(self.type_error("Superclass must be a class."));
+                    };
+                    self.bind_method(&superclass, &name)?;
+                }
+                OpCode::Equal => {
+                    let (left, right) = self.pop_pair();
+                    self.push(Value::Boolean(left == right));
+                }
+                OpCode::NotEqual => {
+                    let (left, right) = self.pop_pair();
+                    self.push(Value::Boolean(left != right));
+                }
+                OpCode::Greater => self.compare(Ordering::is_gt)?,
+                OpCode::GreaterEqual => self.compare(Ordering::is_ge)?,
+                OpCode::Less => self.compare(Ordering::is_lt)?,
+                OpCode::LessEqual => self.compare(Ordering::is_le)?,
+                OpCode::Add => {
+                    let concat = match (self.peek(1), self.peek(0)) {
+                        (Value::String(_), right) => {
+                            matches!(
+                                right,
+                                Value::String(_) | Value::Number(_) | Value::Integer(_)
+                            )
+                        }
+                        (Value::Number(_) | Value::Integer(_), Value::String(_)) => true,
+                        _ => false,
+                    };
+                    if concat {
+                        let (left, right) = self.pop_pair();
+                        self.push(Value::String(Rc::from(format!("{}{}", left, right))));
+                    } else {
+                        self.arithmetic(ArithmeticOp::Add)?;
+                    }
+                }
+                OpCode::Subtract => self.arithmetic(ArithmeticOp::Subtract)?,
+                OpCode::Multiply => self.arithmetic(ArithmeticOp::Multiply)?,
+                OpCode::Divide => self.arithmetic(ArithmeticOp::Divide)?,
+                OpCode::IntDivide => self.arithmetic(ArithmeticOp::IntDivide)?,
+                OpCode::Modulo => self.arithmetic(ArithmeticOp::Modulo)?,
+                OpCode::Power => self.arithmetic(ArithmeticOp::Power)?,
+                OpCode::BitAnd => self.bitwise(BitwiseOp::And)?,
+                OpCode::BitOr => self.bitwise(BitwiseOp::Or)?,
+                OpCode::BitXor => self.bitwise(BitwiseOp::Xor)?,
+                OpCode::ShiftLeft => self.bitwise(BitwiseOp::ShiftLeft)?,
+                OpCode::ShiftRight => self.bitwise(BitwiseOp::ShiftRight)?,
+                OpCode::BitNot => match self.pop() {
+                    Value::Integer(n) => self.push(Value::Integer(!n)),
+                    value => {
+                        return Err(
+                            self.type_error(&format!("Operand must be an integer, got {value}"))
+                        )
+                    }
+                },
+                OpCode::Not => {
+                    let value = self.pop();
+                    self.push(Value::Boolean(!value.is_truthy()));
+                }
+                OpCode::Negate => {
+                    let value = self.pop();
+                    let Some(n) = value.as_number() else {
+                        return Err(
+                            self.type_error(&format!("Operand must be a number, got {:?}", value))
+                        );
+                    };
+                    match numbers::negate(n) {
+                        Ok(result) => self.push(result.into()),
+                        Err(error) => {
+                            return Err(RuntimeError::arithmetic(
+                                error,
+                                self.span(),
+                                format!("-{value}"),
+                            ))
+                        }
+                    }
+                }
+                OpCode::Print => {
+                    let value = self.pop();
+                    println!("{}", value);
+                }
+                OpCode::Jump(offset) => self.frame_mut().ip += offset as usize,
+                OpCode::JumpIfFalse(offset) => {
+                    if !self.peek(0).is_truthy() {
+                        self.frame_mut().ip += offset as usize;
+                    }
+                }
+                OpCode::Loop(offset) => self.frame_mut().ip -= offset as usize,
+                OpCode::GetIter => {
+                    let iterable = self.pop();
+                    let iterator = self.iterate(iterable)?;
+                    self.push(Value::Iterator(Rc::new(RefCell::new(iterator))));
+                }
+                OpCode::ForNext(offset) => {
+                    let Value::Iterator(iterator) = self.peek(0).clone() else {
+                        return Err(self.type_error("Expected an iterator."));
+                    };
+                    match self.next_item(&iterator)? {
+                        Some(item) => self.push(item),
+                        None => self.frame_mut().ip += offset as usize,
+                    }
+                }
+                OpCode::Call(arg_count) => {
+                    let callee = self.peek(arg_count as usize).clone();
+                    self.call_value(callee, arg_count as usize, &[])?;
+                }
+                OpCode::CallNamed(arg_count, names) => {
+                    let names = self.read_names(names)?;
+                    let callee = self.peek(arg_count as usize).clone();
+                    self.call_value(callee, arg_count as usize, &names)?;
+                }
+                OpCode::TailCall(arg_count) => self.tail_call(arg_count as usize, &[])?,
+                OpCode::TailCallNamed(arg_count, names) => {
+                    let names = self.read_names(names)?;
+                    self.tail_call(arg_count as usize, &names)?;
+                }
+                OpCode::JumpIfPassed(param, offset) => {
+                    let passed = self.frame().passed.get(param as usize);
+                    if passed.copied().unwrap_or(true) {
+                        self.frame_mut().ip += offset as usize;
+                    }
+                }
+                OpCode::Closure(index) => {
+                    let Value::Function(function) = self.read_constant(index) else {
+                        return Err(self.type_error("Closure constant must be a function."));
+                    };
+
+                    let mut upvalues = Vec::with_capacity(function.upvalues.len());
+                    for upvalue in &function.upvalues {
+                        let upvalue = if upvalue.is_local {
+                            self.capture_upvalue(self.frame().slots + upvalue.index as usize)
+                        } else {
+                            Rc::clone(&self.frame().closure.upvalues[upvalue.index as usize])
+                        };
+                        upvalues.push(upvalue);
+                    }
+
+                    self.push(Value::Closure(Rc::new(Closure { function, upvalues })));
+                }
+                OpCode::CloseUpvalue => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.pop();
+                }
+                OpCode::Return => {
+                    let result = self.pop();
+                    let frame = self.frames.pop().expect("no frame to return from");
+                    self.close_upvalues(frame.slots);
+                    self.stack.truncate(frame.slots);
+
+                    if self.frames.len() == base {
+                        return Ok(result);
+                    }
+                    self.push(result);
+                }
+                OpCode::Class(index) => {
+                    let name = self.read_string(index);
+                    self.push(Value::Class(Rc::new(RefCell::new(ClassObject {
+                        name: name.to_string(),
+                        methods: HashMap::new(),
+                    }))));
+                }
+                OpCode::Inherit => {
+                    let Value::Class(superclass) = self.peek(1).clone() else {
+                        return Err(self.type_error("Superclass must be a class."));
+                    };
+                    let Value::Class(subclass) = self.pop() else {
+                        return Err(self.type_error("Only classes can inherit."));
+                    };
+
+                    let methods = superclass.borrow().methods.clone();
+                    subclass.borrow_mut().methods.extend(methods);
+                }
+                OpCode::Method(index) => {
+                    let name = self.read_string(index);
+                    let Value::Closure(method) = self.pop() else {
+                        return Err(self.type_error("Method must be a function."));
+                    };
+                    let Value::Class(class) = self.peek(0) else {
+                        return Err(self.type_error("Methods can only be added to classes."));
+                    };
+                    class.borrow_mut().methods.insert(name.to_string(), method);
+                }
+            }
+        }
+    }
+
+    /// Calls `callee` with the `arg_count` values on top of the stack, the
+    /// last `names.len()` of which are passed by name.
+    fn call_value(
+        &mut self,
+        callee: Value,
+        arg_count: usize,
+        names: &[Rc<str>],
+    ) -> Result<(), RuntimeError> {
+        match callee {
+            Value::Closure(closure) => self.call(closure, arg_count, names),
+            Value::Native(native) => {
+                self.check_no_names(names)?;
+                self.check_arity(native.arity, arg_count)?;
+
+                let args_start = self.stack.len() - arg_count;
+                let result = (native.function)(&self.stack[args_start..])
+                    .map_err(|message| self.type_error(&message))?;
+                self.stack.truncate(args_start - 1);
+                self.push(result);
+                Ok(())
+            }
+            Value::Class(class) => {
+                let instance = Value::Instance(Rc::new(RefCell::new(InstanceObject {
+                    class: Rc::clone(&class),
+                    fields: HashMap::new(),
+                })));
+                let callee_slot = self.stack.len() - arg_count - 1;
+                self.stack[callee_slot] = instance;
+
+                let initializer = class.borrow().methods.get("init").cloned();
+                match initializer {
+                    Some(initializer) => self.call(initializer, arg_count, names),
+                    None => {
+                        self.check_no_names(names)?;
+                        self.check_arity(0, arg_count)
+                    }
+                }
+            }
+            Value::BoundMethod(bound) => {
+                let callee_slot = self.stack.len() - arg_count - 1;
+                self.stack[callee_slot] = bound.receiver.clone();
+                self.call(Rc::clone(&bound.method), arg_count, names)
+            }
+            _ => Err(self.type_error(&format!(
+                "Can only call functions and classes. Got: {}",
+                callee
+            ))),
+        }
+    }
+
+    fn call(
+        &mut self,
+        closure: Rc<Closure>,
+        arg_count: usize,
+        names: &[Rc<str>],
+    ) -> Result<(), RuntimeError> {
+        let slots = self.stack.len() - arg_count - 1;
+        let signature = &closure.function.signature;
+        let passed = if names.is_empty() && signature.is_fixed() {
+            self.check_arity(signature.required(), arg_count)?;
+            Vec::new()
+        } else {
+            self.bind_arguments(signature, arg_count, names)?
+        };
+
+        if self.frames.is_empty() {
+            self.stack_use.start();
+        }
+        // The script's own frame doesn't count as a call.
+        if self.frames.len() > self.max_call_depth || self.stack_use.exhausted() {
+            let mut calls: Vec<&str> = self
+                .frames
+                .iter()
+                .map(|frame| frame.closure.function.name.as_str())
+                .collect();
+            calls.push(&closure.function.name);
+            return Err(RuntimeError::stack_overflow(self.span(), &calls));
+        }
+
+        self.frames.push(CallFrame {
+            closure,
+            ip: 0,
+            slots,
+            passed,
+        });
+        Ok(())
+    }
+
+    /// Calls like `call_value`, then lets the callee's frame, if it got one,
+    /// take the place of the caller's so that tail recursion runs in
+    /// constant space.
+    fn tail_call(&mut self, arg_count: usize, names: &[Rc<str>]) -> Result<(), RuntimeError> {
+        let frames = self.frames.len();
+        let callee = self.peek(arg_count).clone();
+        self.call_value(callee, arg_count, names)?;
+        if self.frames.len() == frames {
+            return Ok(());
+        }
+
+        let callee = self.frames.pop().expect("no frame for the tail call");
+        let caller = self.frames.pop().expect("no frame making the tail call");
+        self.close_upvalues(caller.slots);
+        self.stack.drain(caller.slots..callee.slots);
+        self.frames.push(CallFrame {
+            slots: caller.slots,
+            ..callee
+        });
+        Ok(())
+    }
+
+    /// Replaces the arguments on top of the stack with one value per
+    /// parameter, `nil` where the default is still to be computed, and a
+    /// list for the rest parameter. Returns which parameters were passed.
+    fn bind_arguments(
+        &mut self,
+        signature: &Signature,
+        arg_count: usize,
+        names: &[Rc<str>],
+    ) -> Result<Vec<bool>, RuntimeError> {
+        let mut positional = self.stack.split_off(self.stack.len() - arg_count);
+        let named = positional.split_off(arg_count - names.len());
+        let named: Vec<(Rc<str>, Value)> = names.iter().cloned().zip(named).collect();
+
+        let Binding { args, rest } = signature
+            .bind(positional, named)
+            .map_err(|message| self.type_error(&message))?;
+
+        let passed = args.iter().map(Option::is_some).collect();
+        for arg in args {
+            self.push(arg.unwrap_or(Value::Nil));
+        }
+        if signature.has_rest() {
+            self.push(Value::list(rest));
+        }
+        Ok(passed)
+    }
+
+    fn check_arity(&self, arity: usize, arg_count: usize) -> Result<(), RuntimeError> {
+        if arity != arg_count {
+            return Err(self.type_error(&params::arity_error(arity, Some(arity), arg_count)));
+        }
+        Ok(())
+    }
+
+    /// Natives and classes without an initializer have no parameter names.
+    fn check_no_names(&self, names: &[Rc<str>]) -> Result<(), RuntimeError> {
+        match names.first() {
+            Some(name) => Err(self.type_error(&format!("Unknown parameter '{name}'."))),
+            None => Ok(()),
+        }
+    }
+
+    /// Mirrors `Interpreter::iterate`.
+    fn iterate(&mut self, iterable: Value) -> Result<IteratorObject, RuntimeError> {
+        match iterable {
+            Value::List(elements) => Ok(IteratorObject::List(elements, 0)),
+            Value::Map(entries) => {
+                let keys: Vec<Value> = entries.borrow().keys().cloned().collect();
+                Ok(IteratorObject::Snapshot(keys.into_iter()))
+            }
+            Value::String(s) => {
+                let chars: Vec<Value> = s
+                    .chars()
+                    .map(|c| Value::String(Rc::from(c.to_string())))
+                    .collect();
+                Ok(IteratorObject::Snapshot(chars.into_iter()))
+            }
+            Value::Range(range) => Ok(IteratorObject::Range(range)),
+            Value::Instance(_) => {
+                match self.invoke(&iterable, "iterator")? {
+                    iterator @ Value::Instance(_) => Ok(IteratorObject::Object(iterator)),
+                    _ => Err(self
+                        .type_error("iterator() must return an object with hasNext() and next().")),
+                }
+            }
+            other => Err(self.type_error(&format!("Can't iterate over {other}."))),
+        }
+    }
+
+    fn next_item(
+        &mut self,
+        iterator: &Rc<RefCell<IteratorObject>>,
+    ) -> Result<Option<Value>, RuntimeError> {
+        let object = match &mut *iterator.borrow_mut() {
+            IteratorObject::List(elements, next) => {
+                let item = elements.borrow().get(*next).cloned();
+                *next += 1;
+                return Ok(item);
+            }
+            IteratorObject::Snapshot(items) => return Ok(items.next()),
+            IteratorObject::Range(range) => return Ok(range.next().map(Value::Integer)),
+            IteratorObject::Object(object) => object.clone(),
+        };
+
+        if !self.invoke(&object, "hasNext")?.is_truthy() {
+            return Ok(None);
+        }
+        self.invoke(&object, "next").map(Some)
+    }
+
+    /// Calls the method `name` of `receiver` without arguments and runs it to
+    /// completion, for protocols that the VM drives itself.
+    fn invoke(&mut self, receiver: &Value, name: &str) -> Result<Value, RuntimeError> {
+        let Value::Instance(instance) = receiver else {
+            return Err(self.type_error("Only instances have properties."));
+        };
+
+        let base = self.frames.len();
+        let field = instance.borrow().fields.get(name).cloned();
+        match field {
+            Some(field) => self.push(field),
+            None => {
+                self.push(receiver.clone());
+                let class = Rc::clone(&instance.borrow().class);
+                self.bind_method(&class, name)?;
+            }
+        }
+
+        let callee = self.peek(0).clone();
+        self.call_value(callee, 0, &[])?;
+        if self.frames.len() > base {
+            self.run(base)
+        } else {
+            Ok(self.pop())
+        }
+    }
+
+    fn bind_method(
+        &mut self,
+        class: &Rc<RefCell<ClassObject>>,
+        name: &str,
+    ) -> Result<(), RuntimeError> {
+        let method = class.borrow().methods.get(name).cloned();
+        let Some(method) = method else {
+            return Err(RuntimeError::UndefinedProperty(self.synthetic_token(name)));
+        };
+
+        let receiver = self.pop();
+        self.push(Value::BoundMethod(Rc::new(BoundMethod {
+            receiver,
+            method,
+        })));
+        Ok(())
+    }
+
+    fn capture_upvalue(&mut self, slot: usize) -> Rc<RefCell<Upvalue>> {
+        let existing = self
+            .open_upvalues
+            .iter()
+            .find(|upvalue| matches!(*upvalue.borrow(), Upvalue::Open(open) if open == slot));
+
+        if let Some(upvalue) = existing {
+            return Rc::clone(upvalue);
+        }
+
+        let upvalue = Rc::new(RefCell::new(Upvalue::Open(slot)));
+        self.open_upvalues.push(Rc::clone(&upvalue));
+        upvalue
+    }
+
+    fn close_upvalues(&mut self, last: usize) {
+        let stack = &self.stack;
+        self.open_upvalues.retain(|upvalue| {
+            let mut upvalue = upvalue.borrow_mut();
+            match *upvalue {
+                Upvalue::Open(slot) if slot >= last => {
+                    *upvalue = Upvalue::Closed(stack[slot].clone());
+                    false
+                }
+                _ => true,
+            }
+        });
+    }
+
+    fn arithmetic(&mut self, op: ArithmeticOp) -> Result<(), RuntimeError> {
+        let (left, right) = self.pop_pair();
+        match (left.as_number(), right.as_number()) {
+            (Some(l), Some(r)) => match op.apply(l, r) {
+                Ok(result) => {
+                    self.push(result.into());
+                    Ok(())
+                }
+                Err(error) => {
+                    let expression = format!("{left} {} {right}", op.symbol());
+                    Err(RuntimeError::arithmetic(error, self.span(), expression))
+                }
+            },
+            _ if op == ArithmeticOp::Add => Err(self.type_error(&format!(
+                "Operands must be numbers or strings, got {left} and {right}"
+            ))),
+            _ => Err(self.type_error(&format!("Operands must be numbers, got {left} and {right}"))),
+        }
+    }
+
+    fn get_index(&self, object: &Value, index: &Value) -> Result<Value, RuntimeError> {
+        let position = |len: usize| {
+            let index = natives::index_arg(index).map_err(|message| self.type_error(&message))?;
+            collections::position(index, len).map_err(|message| self.type_error(&message))
+        };
+
+        match object {
+            Value::List(elements) => {
+                let elements = elements.borrow();
+                Ok(elements[position(elements.len())?].clone())
+            }
+            Value::String(s) => {
+                let at = position(s.chars().count())?;
+                let c = s.chars().nth(at).unwrap_or_default();
+                Ok(Value::String(Rc::from(c.to_string())))
+            }
+            Value::Map(entries) => match entries.borrow().get(index) {
+                Some(value) => Ok(value.clone()),
+                None if index.is_key() => Err(self.type_error(&collections::undefined_key(index))),
+                None => Err(self.type_error(&collections::invalid_key(index))),
+            },
+            _ => Err(self.type_error("Only lists, maps and strings can be indexed.")),
+        }
+    }
+
+    fn set_index(&self, object: &Value, index: &Value, value: Value) -> Result<(), RuntimeError> {
+        match object {
+            Value::List(elements) => {
+                let index =
+                    natives::index_arg(index).map_err(|message| self.type_error(&message))?;
+                let mut elements = elements.borrow_mut();
+                let at = collections::position(index, elements.len())
+                    .map_err(|message| self.type_error(&message))?;
+                elements[at] = value;
+            }
+            Value::Map(entries) if index.is_key() => {
+                entries.borrow_mut().insert(index.clone(), value);
+            }
+            Value::Map(_) => return Err(self.type_error(&collections::invalid_key(index))),
+            _ => return Err(self.type_error("Only list and map elements can be assigned.")),
+        }
+        Ok(())
+    }
+
+    fn bitwise(&mut self, op: BitwiseOp) -> Result<(), RuntimeError> {
+        let (left, right) = self.pop_pair();
+        match (&left, &right) {
+            (Value::Integer(l), Value::Integer(r)) => match op.apply(*l, *r) {
+                Ok(result) => {
+                    self.push(Value::Integer(result));
+                    Ok(())
+                }
+                Err(error) => {
+                    let expression = format!("{left} {} {right}", op.symbol());
+                    Err(RuntimeError::arithmetic(error, self.span(), expression))
+                }
+            },
+            _ => Err(self.type_error(&format!(
+                "Operands must be integers, got {left} and {right}"
+            ))),
+        }
+    }
+
+    fn compare(&mut self, test: fn(Ordering) -> bool) -> Result<(), RuntimeError> {
+        let (left, right) = self.pop_pair();
+        match (left.as_number(), right.as_number()) {
+            (Some(l), Some(r)) => {
+                self.push(Value::Boolean(numbers::compare(l, r).is_some_and(test)));
+                Ok(())
+            }
+            _ => Err(self.type_error(&format!("Operands must be numbers, got {left} and {right}"))),
+        }
+    }
+
+    fn frame(&self) -> &CallFrame {
+        self.frames.last().expect("no active call frame")
+    }
+
+    fn frame_mut(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().expect("no active call frame")
+    }
+
+    fn read_names(&self, index: u16) -> Result<Vec<Rc<str>>, RuntimeError> {
+        let Value::List(names) = self.read_constant(index) else {
+            return Err(self.type_error("Argument names must be a list."));
+        };
+        let names = names
+            .borrow()
+            .iter()
+            .filter_map(|name| match name {
+                Value::String(name) => Some(Rc::clone(name)),
+                _ => None,
+            })
+            .collect();
+        Ok(names)
+    }
+
+    fn read_constant(&self, index: u16) -> Value {
+        self.frame().closure.function.chunk.constants[index as usize].clone()
+    }
+
+    fn read_string(&self, index: u16) -> Rc<str> {
+        match self.read_constant(index) {
+            Value::String(name) => name,
+            value => Rc::from(value.to_string()),
+        }
+    }
+
+    fn push(&mut self, value: Value) {
+        self.stack.push(value);
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("stack underflow")
+    }
+
+    fn pop_pair(&mut self) -> (Value, Value) {
+        let right = self.pop();
+        let left = self.pop();
+        (left, right)
+    }
+
+    fn peek(&self, distance: usize) -> &Value {
+        &self.stack[self.stack.len() - 1 - distance]
+    }
+
+    fn span(&self) -> Span {
+        self.frames.last().map_or(Span::default(), |frame| {
+            frame.closure.function.chunk.spans[frame.ip.saturating_sub(1)]
+        })
+    }
+
+    fn synthetic_token(&self, name: &str) -> Token {
+        Token::new(
+            TokenType::Identifier,
+            name.to_string(),
+            Literal::Nil,
+            self.span(),
+        )
+    }
+
+    fn type_error(&self, message: &str) -> RuntimeError {
+        RuntimeError::TypeError(self.span(), message.to_string())
+    }
+
+    fn undefined_variable(&self, name: &str) -> RuntimeError {
+        RuntimeError::UndefinedVariable(self.synthetic_token(name))
+    }
+}
@@ -0,0 +1,7 @@
+mod chunk;
+mod compiler;
+mod machine;
+mod value;
+
+pub use machine::Vm;
+pub use value::Value;
@@ -0,0 +1,393 @@
+use crate::models::literals::Literal;
+use crate::models::natives::NativeValue;
+use crate::models::numbers::{self, Number};
+use crate::models::params::Signature;
+use crate::vm::chunk::Chunk;
+use indexmap::IndexMap;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::mem;
+use std::ops::Range;
+use std::rc::Rc;
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Nil,
+    Boolean(bool),
+    Integer(i64),
+    Number(f64),
+    String(Rc<str>),
+    Function(Rc<CompiledFunction>),
+    Closure(Rc<Closure>),
+    Native(Rc<NativeFunction>),
+    Class(Rc<RefCell<ClassObject>>),
+    Instance(Rc<RefCell<InstanceObject>>),
+    BoundMethod(Rc<BoundMethod>),
+    List(Rc<RefCell<Vec<Value>>>),
+    Map(Rc<RefCell<IndexMap<Value, Value>>>),
+    Iterator(Rc<RefCell<IteratorObject>>),
+    Range(Range<i64>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UpvalueRef {
+    pub is_local: bool,
+    pub index: u8,
+}
+
+#[derive(Debug, Default)]
+pub struct CompiledFunction {
+    pub name: String,
+    pub signature: Signature,
+    pub chunk: Chunk,
+    pub upvalues: Vec<UpvalueRef>,
+}
+
+#[derive(Debug)]
+pub enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+#[derive(Debug)]
+pub struct Closure {
+    pub function: Rc<CompiledFunction>,
+    pub upvalues: Vec<Rc<RefCell<Upvalue>>>,
+}
+
+#[derive(Debug)]
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: usize,
+    pub function: fn(&[Value]) -> Result<Value, String>,
+}
+
+#[derive(Debug)]
+pub struct ClassObject {
+    pub name: String,
+    pub methods: HashMap<String, Rc<Closure>>,
+}
+
+#[derive(Debug)]
+pub struct InstanceObject {
+    pub class: Rc<RefCell<ClassObject>>,
+    pub fields: HashMap<String, Value>,
+}
+
+/// The state of a running for-in loop, mirroring the interpreter's.
+#[derive(Debug)]
+pub enum IteratorObject {
+    List(Rc<RefCell<Vec<Value>>>, usize),
+    Snapshot(std::vec::IntoIter<Value>),
+    Object(Value),
+    Range(Range<i64>),
+}
+
+#[derive(Debug)]
+pub struct BoundMethod {
+    pub receiver: Value,
+    pub method: Rc<Closure>,
+}
+
+impl Value {
+    /// Converts primitive literals and lists of them to VM values. Functions,
+    /// classes and instances of the tree-walking interpreter have no VM
+    /// counterpart and become `nil`.
+    pub fn from_literal(literal: &Literal) -> Value {
+        Value::from_literal_within(literal, &mut HashMap::new())
+    }
+
+    /// Converts `literal`, reusing the copies in `copied` of lists and maps
+    /// already seen so that shared and self-referential ones keep their shape.
+    fn from_literal_within(literal: &Literal, copied: &mut HashMap<*const (), Value>) -> Value {
+        match literal {
+            Literal::List(elements) => {
+                let ptr = Rc::as_ptr(elements) as *const ();
+                if let Some(copy) = copied.get(&ptr) {
+                    return copy.clone();
+                }
+                let copy = Rc::new(RefCell::new(Vec::new()));
+                copied.insert(ptr, Value::List(Rc::clone(&copy)));
+                for element in elements.borrow().iter() {
+                    let element = Value::from_literal_within(element, copied);
+                    copy.borrow_mut().push(element);
+                }
+                Value::List(copy)
+            }
+            Literal::Map(entries) => {
+                let ptr = Rc::as_ptr(entries) as *const ();
+                if let Some(copy) = copied.get(&ptr) {
+                    return copy.clone();
+                }
+                let copy = Rc::new(RefCell::new(IndexMap::new()));
+                copied.insert(ptr, Value::Map(Rc::clone(&copy)));
+                for (key, value) in entries.borrow().iter() {
+                    let key = Value::from_literal_within(key, copied);
+                    let value = Value::from_literal_within(value, copied);
+                    copy.borrow_mut().insert(key, value);
+                }
+                Value::Map(copy)
+            }
+            Literal::Boolean(b) => Value::Boolean(*b),
+            Literal::Integer(n) => Value::Integer(*n),
+            Literal::Number(n) => Value::Number(*n),
+            Literal::String(s) => Value::String(Rc::from(s.as_str())),
+            Literal::Range(range) => Value::Range(range.clone()),
+            _ => Value::Nil,
+        }
+    }
+
+    /// Converts primitive values and lists back to a `Literal`. Functions,
+    /// classes and instances live only inside the VM and come back as `nil`.
+    pub fn to_literal(&self) -> Literal {
+        self.to_literal_within(&mut HashMap::new())
+    }
+
+    /// Mirrors `Value::from_literal_within`.
+    fn to_literal_within(&self, copied: &mut HashMap<*const (), Literal>) -> Literal {
+        match self {
+            Value::List(elements) => {
+                let ptr = Rc::as_ptr(elements) as *const ();
+                if let Some(copy) = copied.get(&ptr) {
+                    return copy.clone();
+                }
+                let copy = Rc::new(RefCell::new(Vec::new()));
+                copied.insert(ptr, Literal::List(Rc::clone(&copy)));
+                for element in elements.borrow().iter() {
+                    let element = element.to_literal_within(copied);
+                    copy.borrow_mut().push(element);
+                }
+                Literal::List(copy)
+            }
+            Value::Map(entries) => {
+                let ptr = Rc::as_ptr(entries) as *const ();
+                if let Some(copy) = copied.get(&ptr) {
+                    return copy.clone();
+                }
+                let copy = Rc::new(RefCell::new(IndexMap::new()));
+                copied.insert(ptr, Literal::Map(Rc::clone(&copy)));
+                for (key, value) in entries.borrow().iter() {
+                    let key = key.to_literal_within(copied);
+                    let value = value.to_literal_within(copied);
+                    copy.borrow_mut().insert(key, value);
+                }
+                Literal::Map(copy)
+            }
+            Value::Boolean(b) => Literal::Boolean(*b),
+            Value::Integer(n) => Literal::Integer(*n),
+            Value::Number(n) => Literal::Number(*n),
+            Value::String(s) => Literal::String(s.to_string()),
+            Value::Range(range) => Literal::Range(range.clone()),
+            _ => Literal::Nil,
+        }
+    }
+
+    pub fn list(elements: Vec<Value>) -> Self {
+        Value::List(Rc::new(RefCell::new(elements)))
+    }
+
+    pub fn map(entries: IndexMap<Value, Value>) -> Self {
+        Value::Map(Rc::new(RefCell::new(entries)))
+    }
+
+    /// Mirrors `Literal::is_key`.
+    pub fn is_key(&self) -> bool {
+        matches!(
+            self,
+            Value::Nil
+                | Value::Boolean(_)
+                | Value::Integer(_)
+                | Value::Number(_)
+                | Value::String(_)
+        )
+    }
+
+    pub fn as_number(&self) -> Option<Number> {
+        match self {
+            Value::Integer(n) => Some(Number::Integer(*n)),
+            Value::Number(n) => Some(Number::Float(*n)),
+            _ => None,
+        }
+    }
+
+    /// Mirrors `Literal::fmt_nested`.
+    fn fmt_nested(&self, f: &mut Formatter<'_>, open: &mut Vec<*const ()>) -> std::fmt::Result {
+        match self {
+            Value::String(s) => write!(f, "\"{}\"", s),
+            _ => self.fmt_within(f, open),
+        }
+    }
+
+    /// Mirrors `Interpreter::is_truthy` so both backends agree on conditions.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Nil => false,
+            Value::Boolean(b) => *b,
+            Value::Integer(n) => *n != 0,
+            Value::Number(n) => *n != 0.0,
+            Value::String(s) => !s.is_empty(),
+            Value::List(elements) => !elements.borrow().is_empty(),
+            Value::Map(entries) => !entries.borrow().is_empty(),
+            Value::Range(range) => !range.is_empty(),
+            Value::Class(_) | Value::Instance(_) => true,
+            _ => false,
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Boolean(a), Value::Boolean(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Function(a), Value::Function(b)) => Rc::ptr_eq(a, b),
+            (Value::Closure(a), Value::Closure(b)) => Rc::ptr_eq(a, b),
+            (Value::Native(a), Value::Native(b)) => a.name == b.name,
+            (Value::Class(a), Value::Class(b)) => Rc::ptr_eq(a, b),
+            (Value::Instance(a), Value::Instance(b)) => Rc::ptr_eq(a, b),
+            (Value::BoundMethod(a), Value::BoundMethod(b)) => Rc::ptr_eq(a, b),
+            (Value::List(a), Value::List(b)) => Rc::ptr_eq(a, b),
+            (Value::Map(a), Value::Map(b)) => Rc::ptr_eq(a, b),
+            (Value::Range(a), Value::Range(b)) => a == b,
+            // Integers and floats compare by value, as in `Literal`.
+            _ => match (self.as_number(), other.as_number()) {
+                (Some(a), Some(b)) => numbers::compare(a, b) == Some(Ordering::Equal),
+                _ => false,
+            },
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.fmt_within(f, &mut Vec::new())
+    }
+}
+
+impl Value {
+    /// Mirrors `Literal::fmt_within`.
+    fn fmt_within(&self, f: &mut Formatter<'_>, open: &mut Vec<*const ()>) -> std::fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::Integer(n) => write!(f, "{}", n),
+            Value::Number(n) => write!(f, "{:?}", n),
+            Value::String(s) => write!(f, "{}", s),
+            Value::Function(function) => write!(f, "<lox fn {}>", function.name),
+            Value::Closure(closure) => write!(f, "<lox fn {}>", closure.function.name),
+            Value::Native(native) => write!(f, "<native fn {}>", native.name),
+            Value::Class(class) => write!(f, "{}", class.borrow().name),
+            Value::Instance(instance) => {
+                write!(f, "{} instance", instance.borrow().class.borrow().name)
+            }
+            Value::BoundMethod(bound) => write!(f, "<lox fn {}>", bound.method.function.name),
+            Value::List(elements) => {
+                let ptr = Rc::as_ptr(elements) as *const ();
+                if open.contains(&ptr) {
+                    return write!(f, "[...]");
+                }
+                open.push(ptr);
+                write!(f, "[")?;
+                for (i, element) in elements.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    element.fmt_nested(f, open)?;
+                }
+                open.pop();
+                write!(f, "]")
+            }
+            Value::Iterator(_) => write!(f, "<iterator>"),
+            Value::Range(range) => write!(f, "<range {}..{}>", range.start, range.end),
+            Value::Map(entries) => {
+                let ptr = Rc::as_ptr(entries) as *const ();
+                if open.contains(&ptr) {
+                    return write!(f, "{{...}}");
+                }
+                open.push(ptr);
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    key.fmt_nested(f, open)?;
+                    write!(f, ": ")?;
+                    value.fmt_nested(f, open)?;
+                }
+                open.pop();
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+impl Eq for Value {}
+
+/// Agrees with `PartialEq`, as `Literal`'s hashing does.
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Value::String(s) => s.hash(state),
+            Value::Boolean(b) => b.hash(state),
+            Value::Integer(n) => numbers::hash(Number::Integer(*n), state),
+            Value::Number(n) => numbers::hash(Number::Float(*n), state),
+            _ => mem::discriminant(self).hash(state),
+        }
+    }
+}
+
+impl NativeValue for Value {
+    fn nil() -> Self {
+        Value::Nil
+    }
+
+    fn boolean(b: bool) -> Self {
+        Value::Boolean(b)
+    }
+
+    fn new_list(elements: Vec<Self>) -> Self {
+        Value::list(elements)
+    }
+
+    fn new_range(range: Range<i64>) -> Self {
+        Value::Range(range)
+    }
+
+    fn number(&self) -> Option<Number> {
+        self.as_number()
+    }
+
+    fn string(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn list(&self) -> Option<&Rc<RefCell<Vec<Self>>>> {
+        match self {
+            Value::List(elements) => Some(elements),
+            _ => None,
+        }
+    }
+
+    fn map(&self) -> Option<&Rc<RefCell<IndexMap<Self, Self>>>> {
+        match self {
+            Value::Map(entries) => Some(entries),
+            _ => None,
+        }
+    }
+}
+
+impl From<Number> for Value {
+    fn from(number: Number) -> Self {
+        match number {
+            Number::Integer(n) => Value::Integer(n),
+            Number::Float(n) => Value::Number(n),
+        }
+    }
+}
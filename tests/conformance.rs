@@ -0,0 +1,208 @@
+//! Runs every Lox script under `tests/lox` and checks its output against the
+//! annotations in the script itself:
+//!
+//! - `// expect: <value>` - a line printed to stdout.
+//! - `// expect runtime error: <message>` - a runtime error raised on that line.
+//! - `// Error at '<lexeme>': <message>`, `// Error at end: <message>` - a static
+//!   (parse or resolve) error on that line.
+//! - `// Error: <message>` - a scanner error on that line.
+//!
+//! Static errors may be prefixed with `[line N]` when they are reported on a
+//! line other than the annotation's. The source snippet and the call trace
+//! printed under each error are not compared. The exit code must match as
+//! well: 65 for static errors, 70 for runtime errors and 0 otherwise.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Mirrors `ExitCode` in `src/models/constants.rs`.
+const SUCCESS: i32 = 0;
+const DATA_ERROR: i32 = 65;
+const RUNTIME_ERROR: i32 = 70;
+
+const BACKENDS: [&str; 2] = ["tree", "vm"];
+
+#[derive(Debug, Default)]
+struct Expectations {
+    output: Vec<String>,
+    errors: Vec<String>,
+    exit_code: i32,
+}
+
+impl Expectations {
+    fn parse(source: &str) -> Self {
+        let mut expectations = Self::default();
+
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let Some((_, comment)) = line.split_once("// ") else {
+                continue;
+            };
+
+            if let Some(value) = comment.strip_prefix("expect: ") {
+                expectations.output.push(value.to_string());
+            } else if let Some(message) = comment.strip_prefix("expect runtime error: ") {
+                expectations
+                    .errors
+                    .push(format!("line {}: {}", line_number, message));
+                expectations.exit_code = RUNTIME_ERROR;
+            } else if let Some(error) = Self::parse_static_error(comment, line_number) {
+                expectations.errors.push(error);
+                expectations.exit_code = DATA_ERROR;
+            }
+        }
+
+        expectations
+    }
+
+    fn parse_static_error(comment: &str, line_number: usize) -> Option<String> {
+        let (line_number, comment) = match comment.strip_prefix("[line ") {
+            Some(rest) => {
+                let (number, rest) = rest.split_once("] ")?;
+                (number.parse().ok()?, rest)
+            }
+            None => (line_number, comment),
+        };
+
+        if let Some(rest) = comment.strip_prefix("Error at ") {
+            return Some(format!("line {} at {}", line_number, rest));
+        }
+
+        comment
+            .strip_prefix("Error: ")
+            .map(|message| format!("line {}: {}", line_number, message))
+    }
+}
+
+fn collect_scripts(dir: &Path, scripts: &mut Vec<PathBuf>) {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap_or_else(|e| panic!("cannot read {}: {}", dir.display(), e))
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            collect_scripts(&path, scripts);
+        } else if matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some("lox" | "rlox")
+        ) {
+            scripts.push(path);
+        }
+    }
+}
+
+/// Whether `line` belongs to the source snippet under a diagnostic, such as
+/// ` --> 3:7`, `  |` or `3 | print x;`.
+fn is_snippet_line(line: &str) -> bool {
+    let rest = line
+        .trim_start()
+        .trim_start_matches(|c: char| c.is_ascii_digit())
+        .trim_start();
+    rest.starts_with('|') || rest.starts_with("-->")
+}
+
+/// Whether `line` belongs to the call trace under a runtime error, such as
+/// `[line 3] in f()` or `[... 12 more calls]`.
+fn is_trace_line(line: &str) -> bool {
+    let is_number = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    if let Some((number, _)) = line
+        .strip_prefix("[line ")
+        .and_then(|rest| rest.split_once("] in "))
+    {
+        return is_number(number);
+    }
+    line.strip_prefix("[... ")
+        .and_then(|rest| rest.strip_suffix(" more calls]"))
+        .is_some_and(is_number)
+}
+
+fn run_script(path: &Path, backend: &str, expectations: &Expectations) -> Result<(), String> {
+    let output = Command::new(env!("CARGO_BIN_EXE_lox-interpreter-rust"))
+        .arg(format!("--backend={}", backend))
+        .arg(path)
+        .output()
+        .map_err(|e| format!("failed to run interpreter: {}", e))?;
+
+    // The interpreter announces errors on stdout before listing them on stderr.
+    let stdout: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| *line != "Errors:" && *line != "Runtime errors:")
+        .map(str::to_string)
+        .collect();
+    let stderr: Vec<String> = String::from_utf8_lossy(&output.stderr)
+        .lines()
+        .filter(|line| !is_snippet_line(line) && !is_trace_line(line))
+        .map(str::to_string)
+        .collect();
+    let exit_code = output.status.code().unwrap_or(-1);
+
+    let mut problems = Vec::new();
+    if stdout != expectations.output {
+        problems.push(format!(
+            "stdout mismatch\n    expected: {:?}\n    actual:   {:?}",
+            expectations.output, stdout
+        ));
+    }
+    if stderr != expectations.errors {
+        problems.push(format!(
+            "stderr mismatch\n    expected: {:?}\n    actual:   {:?}",
+            expectations.errors, stderr
+        ));
+    }
+    if exit_code != expectations.exit_code {
+        problems.push(format!(
+            "exit code mismatch: expected {}, got {}",
+            expectations.exit_code, exit_code
+        ));
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems.join("\n  "))
+    }
+}
+
+#[test]
+fn lox_scripts_match_expectations() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("lox");
+    let mut scripts = Vec::new();
+    collect_scripts(&root, &mut scripts);
+    assert!(
+        !scripts.is_empty(),
+        "no scripts found in {}",
+        root.display()
+    );
+
+    let mut failures = Vec::new();
+    for script in &scripts {
+        // Some scripts are deliberately not valid UTF-8.
+        let source = String::from_utf8_lossy(&fs::read(script).unwrap()).into_owned();
+        let expectations = Expectations::parse(&source);
+        assert!(
+            expectations.exit_code != SUCCESS || !expectations.output.is_empty(),
+            "{} has no expectations",
+            script.display()
+        );
+
+        for backend in BACKENDS {
+            if let Err(problem) = run_script(script, backend, &expectations) {
+                let name = script.strip_prefix(&root).unwrap_or(script);
+                failures.push(format!("{} [{}]:\n  {}", name.display(), backend, problem));
+            }
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} of {} script runs failed:\n\n{}",
+        failures.len(),
+        scripts.len() * BACKENDS.len(),
+        failures.join("\n\n")
+    );
+}
@@ -0,0 +1,335 @@
+use std::cell::Cell;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::rc::Rc;
+use std::thread;
+
+use lox_interpreter_rust::{parse, Backend, Frame, Literal, Lox, LoxError, Stmt, TokenType};
+
+const BACKENDS: [Backend; 2] = [Backend::TreeWalker, Backend::Vm];
+
+#[test]
+fn run_returns_value_of_trailing_expression() {
+    for backend in BACKENDS {
+        let mut lox = Lox::with_backend(backend);
+        assert_eq!(lox.run("1 + 2;").unwrap(), Literal::Number(3.0));
+        assert_eq!(lox.run("var a = 1;").unwrap(), Literal::Nil);
+    }
+}
+
+#[test]
+fn globals_survive_between_runs() {
+    for backend in BACKENDS {
+        let mut lox = Lox::with_backend(backend);
+        lox.run("var greeting = \"hello\";").unwrap();
+        lox.run("greeting = greeting + \" world\";").unwrap();
+
+        assert_eq!(
+            lox.get_global("greeting"),
+            Some(Literal::String("hello world".to_string()))
+        );
+        assert_eq!(lox.get_global("missing"), None);
+    }
+}
+
+#[test]
+fn host_defined_globals_are_visible_to_scripts() {
+    for backend in BACKENDS {
+        let mut lox = Lox::with_backend(backend);
+        lox.define_global("limit", Literal::Number(3.0));
+
+        let result = lox.run("limit * 2;").unwrap();
+        assert_eq!(result, Literal::Number(6.0));
+    }
+}
+
+#[test]
+fn call_lox_function_from_rust() {
+    for backend in BACKENDS {
+        let mut lox = Lox::with_backend(backend);
+        lox.run("fun greet(name) { return \"Hi, \" + name; }")
+            .unwrap();
+
+        let result = lox
+            .call("greet", vec![Literal::String("Lox".to_string())])
+            .unwrap();
+        assert_eq!(result, Literal::String("Hi, Lox".to_string()));
+    }
+}
+
+#[test]
+fn calls_from_rust_fill_in_defaults_and_rest() {
+    for backend in BACKENDS {
+        let mut lox = Lox::with_backend(backend);
+        lox.run("fun describe(a, b = 2, ...rest) { return [a, b, len(rest)]; }")
+            .unwrap();
+
+        let result = lox.call("describe", vec![Literal::Integer(1)]).unwrap();
+        assert_eq!(result.to_string(), "[1, 2, 0]");
+
+        let args = (1..=4).map(Literal::Integer).collect();
+        let result = lox.call("describe", args).unwrap();
+        assert_eq!(result.to_string(), "[1, 2, 2]");
+    }
+}
+
+#[test]
+fn call_reports_runtime_errors() {
+    for backend in BACKENDS {
+        let mut lox = Lox::with_backend(backend);
+        lox.run("fun fail() { return 1 / 0; }").unwrap();
+
+        assert!(matches!(
+            lox.call("fail", vec![]),
+            Err(LoxError::Runtime(_))
+        ));
+        assert!(matches!(
+            lox.call("missing", vec![]),
+            Err(LoxError::Runtime(_))
+        ));
+    }
+}
+
+#[test]
+fn errors_are_grouped_by_phase() {
+    let mut lox = Lox::new();
+
+    assert!(matches!(lox.run("print @;"), Err(LoxError::Scan(_))));
+    assert!(matches!(lox.run("print ;"), Err(LoxError::Parse(_))));
+    assert!(matches!(lox.run("return 1;"), Err(LoxError::Parse(_))));
+    assert!(matches!(lox.run("print nope;"), Err(LoxError::Runtime(_))));
+}
+
+#[test]
+fn natives_can_capture_host_state() {
+    let calls = Rc::new(Cell::new(0));
+    let counter = Rc::clone(&calls);
+
+    let mut lox = Lox::new();
+    lox.interpreter()
+        .unwrap()
+        .define_native("tick", 0, move |_, _| {
+            counter.set(counter.get() + 1);
+            Ok(Literal::Number(counter.get() as f64))
+        });
+
+    let result = lox.run("tick(); tick(); tick();").unwrap();
+    assert_eq!(result, Literal::Number(3.0));
+    assert_eq!(calls.get(), 3);
+}
+
+#[test]
+fn natives_can_only_be_registered_on_the_tree_walker() {
+    assert!(Lox::with_backend(Backend::TreeWalker)
+        .interpreter()
+        .is_some());
+    assert!(Lox::with_backend(Backend::Vm).interpreter().is_none());
+}
+
+#[test]
+fn natives_can_call_back_into_lox() {
+    let mut lox = Lox::new();
+    lox.interpreter()
+        .unwrap()
+        .define_native("twice", 2, |interpreter, args| {
+            let once = interpreter.call(&args[0], vec![args[1].clone()])?;
+            interpreter.call(&args[0], vec![once])
+        });
+
+    let result = lox
+        .run("fun double(n) { return n * 2; } twice(double, 5);")
+        .unwrap();
+    assert_eq!(result, Literal::Number(20.0));
+}
+
+#[test]
+fn errors_render_with_underlined_source() {
+    let source = "var a = 1;\nprint a +  nope;";
+
+    for backend in BACKENDS {
+        let mut lox = Lox::with_backend(backend);
+        let error = lox.run(source).unwrap_err();
+
+        assert_eq!(
+            error.render(source),
+            "line 2: Undefined variable 'nope'.\n \
+             --> 2:12\n  \
+             |\n\
+             2 | print a +  nope;\n  \
+             |            ^^^^"
+        );
+    }
+}
+
+#[test]
+fn parse_attaches_doc_comments_to_declarations() {
+    let source = "
+        /// Greets people.
+        /// Politely.
+        class Greeter {
+            /// Says hi.
+            hi() {}
+            bye() {}
+        }
+
+        /// The answer.
+        var answer = 42;
+
+        // Not a doc comment.
+        fun plain() {}
+    ";
+
+    let stmts = parse(source).unwrap();
+    assert_eq!(stmts[0].doc(), Some("Greets people.\nPolitely."));
+    assert_eq!(stmts[1].doc(), Some("The answer."));
+    assert_eq!(stmts[2].doc(), None);
+
+    let Stmt::Class(_, _, methods, _) = &stmts[0] else {
+        panic!("expected a class, got {}", stmts[0]);
+    };
+    assert_eq!(methods[0].doc(), Some("Says hi."));
+    assert_eq!(methods[1].doc(), None);
+
+    let Stmt::Function(name, params, ..) = &methods[0] else {
+        panic!("expected a method, got {}", methods[0]);
+    };
+    assert_eq!(name.lexeme, "hi");
+    assert_eq!(name.token_type, TokenType::Identifier);
+    assert!(params.is_empty());
+}
+
+#[test]
+fn lists_cross_the_host_boundary() {
+    for backend in BACKENDS {
+        let mut lox = Lox::with_backend(backend);
+        lox.define_global("xs", Literal::list(vec![Literal::Integer(1)]));
+
+        let result = lox.run("push(xs, 2); xs;").unwrap();
+        let Literal::List(elements) = result else {
+            panic!("expected a list, got {result}");
+        };
+        assert_eq!(
+            *elements.borrow(),
+            vec![Literal::Integer(1), Literal::Integer(2)]
+        );
+    }
+}
+
+#[test]
+fn equal_literals_hash_alike() {
+    let hash = |literal: Literal| {
+        let mut hasher = DefaultHasher::new();
+        literal.hash(&mut hasher);
+        hasher.finish()
+    };
+
+    assert_eq!(hash(Literal::Integer(1)), hash(Literal::Number(1.0)));
+    assert_eq!(hash(Literal::Integer(0)), hash(Literal::Number(-0.0)));
+    assert_eq!(
+        hash(Literal::String("key".to_string())),
+        hash(Literal::String("key".to_string()))
+    );
+    assert_ne!(Literal::Integer(1), Literal::String("1".to_string()));
+}
+
+#[test]
+fn call_depth_limit_is_configurable() {
+    for backend in BACKENDS {
+        let mut lox = Lox::with_backend(backend);
+        lox.set_max_call_depth(10);
+        lox.run("fun depth(n) { if (n == 0) return 0; return 1 + depth(n - 1); }")
+            .unwrap();
+
+        assert_eq!(lox.run("depth(9);").unwrap(), Literal::Number(9.0));
+        let Err(LoxError::Runtime(errors)) = lox.run("depth(10);") else {
+            panic!("expected a stack overflow on {backend:?}");
+        };
+        assert_eq!(
+            errors[0].to_string(),
+            "line 1: Stack overflow: depth -> depth."
+        );
+    }
+}
+
+#[test]
+fn default_limits_fit_a_default_thread_stack() {
+    for backend in BACKENDS {
+        // Spawned threads get the default 2 MiB stack.
+        let error = thread::spawn(move || {
+            let mut lox = Lox::with_backend(backend);
+            match lox.run("fun f(n) { return f(n + 1) + 1; }\nf(0);") {
+                Err(LoxError::Runtime(errors)) => errors[0].to_string(),
+                other => panic!("expected a stack overflow on {backend:?}, got {other:?}"),
+            }
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(error, "line 1: Stack overflow: f -> f.");
+    }
+}
+
+fn frame(function: Option<&str>, line: usize) -> Frame {
+    Frame {
+        function: function.map(str::to_string),
+        line,
+    }
+}
+
+#[test]
+fn runtime_errors_in_calls_carry_a_trace() {
+    let source = "fun inner() {\n  return 1 / 0;\n}\nfun outer() { return inner() + 1; }\nouter();";
+    for backend in BACKENDS {
+        let mut lox = Lox::with_backend(backend);
+        let error = lox.run(source).unwrap_err();
+        let LoxError::Runtime(errors) = &error else {
+            panic!("expected a runtime error on {backend:?}");
+        };
+
+        assert_eq!(
+            errors[0].trace(),
+            [
+                frame(Some("inner"), 2),
+                frame(Some("outer"), 4),
+                frame(None, 5)
+            ]
+        );
+        assert!(error
+            .render(source)
+            .ends_with("[line 2] in inner()\n[line 4] in outer()\n[line 5] in script"));
+    }
+}
+
+#[test]
+fn traces_of_calls_from_rust_start_at_the_callee() {
+    for backend in BACKENDS {
+        let mut lox = Lox::with_backend(backend);
+        lox.run("fun fail() { return 1 / 0; }").unwrap();
+
+        let Err(LoxError::Runtime(errors)) = lox.call("fail", vec![]) else {
+            panic!("expected a runtime error on {backend:?}");
+        };
+        assert_eq!(errors[0].trace(), [frame(Some("fail"), 1)]);
+
+        // Errors outside of any function have no trace.
+        let Err(LoxError::Runtime(errors)) = lox.run("1 / 0;") else {
+            panic!("expected a runtime error on {backend:?}");
+        };
+        assert!(errors[0].trace().is_empty());
+    }
+}
+
+#[test]
+fn natives_calling_back_into_lox_keep_the_trace() {
+    let mut lox = Lox::new();
+    lox.interpreter()
+        .unwrap()
+        .define_native("apply", 1, |interpreter, args| {
+            interpreter.call(&args[0], vec![])
+        });
+
+    let Err(LoxError::Runtime(errors)) = lox.run("fun fail() { return nil + 1; }\napply(fail);")
+    else {
+        panic!("expected a runtime error");
+    };
+    assert_eq!(errors[0].trace(), [frame(Some("fail"), 1), frame(None, 2)]);
+}